@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+
+use crate::errors::ChipError;
+
+/// Assemble Octo-style source code into a program that can be loaded with
+/// [`Chip8::load_rom`](crate::Chip8::load_rom).
+///
+/// The base address is where the program will be loaded and is used to resolve labels,
+/// typically [`Config::rom_base_addr`](crate::Config::rom_base_addr). Execution starts at
+/// the first instruction of the source.
+///
+/// The supported subset of the Octo syntax is:
+/// - Comments starting with `#` and ending at the end of the line.
+/// - Labels defined with `: name`, called with `:call name` or just `name`.
+/// - `:const name value`, `:alias name register` and `:byte value`.
+/// - Every CHIP-8, Super-Chip and XO-CHIP instruction, such as `v0 := 5`, `i := long label`,
+///   `sprite v0 v1 8` or `save v2 - v5`.
+/// - `if ... then`, `if ... begin ... else ... end` and `loop ... while ... again`, with the
+///   `==`, `!=`, `key` and `-key` conditions.
+/// - Numbers written as bytes, such as sprite data. Numbers can be decimal, hexadecimal
+///   with a `0x` prefix or binary with a `0b` prefix.
+///
+/// Any error is reported as a [`ChipError::AssemblyError`] holding the line it occurred on.
+pub fn assemble(source: &str, base_address: usize) -> Result<Vec<u8>, ChipError> {
+    let mut assembler = Assembler::new(source, base_address);
+    while let Some(token) = assembler.next_token() {
+        assembler.statement(token)?;
+    }
+
+    assembler.finish()
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+// An open control flow structure waiting for its closing keyword
+enum Block {
+    Loop {
+        start: usize,
+        exits: Vec<usize>,
+        line: usize,
+    },
+    If {
+        jump: usize,
+        line: usize,
+    },
+    Else {
+        jump: usize,
+        line: usize,
+    },
+}
+
+// A reference to a label that is patched once every label is known
+struct Fixup<'a> {
+    offset: usize,
+    label: &'a str,
+    line: usize,
+    long: bool,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    base_address: usize,
+    output: Vec<u8>,
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<Fixup<'a>>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(source: &'a str, base_address: usize) -> Self {
+        let tokens = source
+            .lines()
+            .enumerate()
+            .flat_map(|(index, line)| {
+                let code = line.split('#').next().unwrap_or_default();
+                code.split_whitespace().map(move |text| Token {
+                    text,
+                    line: index + 1,
+                })
+            })
+            .collect();
+
+        Assembler {
+            tokens,
+            position: 0,
+            base_address,
+            output: Vec::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+
+        token
+    }
+
+    // The next token of a statement that isn't allowed to end yet
+    fn operand(&mut self, previous: Token) -> Result<Token<'a>, ChipError> {
+        self.next_token().ok_or_else(|| {
+            error(
+                previous.line,
+                format!("unexpected end of source after `{}`", previous.text),
+            )
+        })
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.text)
+    }
+
+    fn expect(&mut self, previous: Token, expected: &str) -> Result<Token<'a>, ChipError> {
+        let token = self.operand(previous)?;
+        if token.text != expected {
+            return Err(unexpected(token, &format!("`{expected}`")));
+        }
+
+        Ok(token)
+    }
+
+    fn address(&self) -> usize {
+        self.base_address + self.output.len()
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.output.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn statement(&mut self, token: Token<'a>) -> Result<(), ChipError> {
+        match token.text {
+            ":" => {
+                let name = self.identifier(token)?;
+                if self.labels.insert(name.text, self.address()).is_some() {
+                    return Err(error(
+                        name.line,
+                        format!("the label `{}` is already defined", name.text),
+                    ));
+                }
+            }
+            ":const" => {
+                let name = self.identifier(token)?;
+                let value = self.operand(name)?;
+                let value = self
+                    .value(value.text)
+                    .ok_or_else(|| unexpected(value, "a number"))?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.identifier(token)?;
+                let register = self.register(name)?;
+                self.aliases.insert(name.text, register);
+            }
+            ":call" => {
+                let target = self.operand(token)?;
+                self.emit_address(0x2000, target)?;
+            }
+            ":byte" => {
+                let value = self.operand(token)?;
+                let value = self.byte(value)?;
+                self.output.push(value);
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "scroll-down" => {
+                let n = self.nibble(token, 0xF)?;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.nibble(token, 0xF)?;
+                self.emit(0x00D0 | n);
+            }
+            "jump" => {
+                let target = self.operand(token)?;
+                self.emit_address(0x1000, target)?;
+            }
+            "jump0" => {
+                let target = self.operand(token)?;
+                self.emit_address(0xB000, target)?;
+            }
+            "sprite" => {
+                let x = self.register(token)?;
+                let y = self.register(token)?;
+                let n = self.nibble(token, 0xF)?;
+                self.emit(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n);
+            }
+            "save" | "load" => {
+                let x = self.register(token)?;
+                let (save, load) = match self.peek() == Some("-") {
+                    true => {
+                        self.position += 1;
+                        let y = (self.register(token)? as u16) << 4;
+                        (0x5002 | y, 0x5003 | y)
+                    }
+                    false => (0xF055, 0xF065),
+                };
+                let opcode = if token.text == "save" { save } else { load };
+                self.emit(opcode | (x as u16) << 8);
+            }
+            "saveflags" => self.emit_register(0xF075, token)?,
+            "loadflags" => self.emit_register(0xF085, token)?,
+            "bcd" => self.emit_register(0xF033, token)?,
+            "plane" => {
+                let n = self.nibble(token, 0x3)?;
+                self.emit(0xF001 | n << 8);
+            }
+            "audio" => self.emit(0xF002),
+            "delay" | "buzzer" | "pitch" => {
+                let assign = self.expect(token, ":=")?;
+                let opcode = match token.text {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.emit_register(opcode, assign)?;
+            }
+            "i" => self.index_statement(token)?,
+            "if" => {
+                let (skip_true, skip_false) = self.condition(token)?;
+                let keyword = self.operand(token)?;
+                match keyword.text {
+                    "then" => self.emit(skip_false),
+                    "begin" => {
+                        self.emit(skip_true);
+                        let jump = self.placeholder();
+                        self.blocks.push(Block::If {
+                            jump,
+                            line: token.line,
+                        });
+                    }
+                    _ => return Err(unexpected(keyword, "`then` or `begin`")),
+                }
+            }
+            "else" => {
+                let Some(Block::If { jump: if_jump, .. }) = self.blocks.pop() else {
+                    return Err(error(
+                        token.line,
+                        "`else` without a matching `if ... begin`",
+                    ));
+                };
+                let jump = self.placeholder();
+                self.patch(if_jump, self.address() as i32, token.line, false)?;
+                self.blocks.push(Block::Else {
+                    jump,
+                    line: token.line,
+                });
+            }
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => {
+                    self.patch(jump, self.address() as i32, token.line, false)?;
+                }
+                _ => return Err(error(token.line, "`end` without a matching `if ... begin`")),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.address(),
+                exits: Vec::new(),
+                line: token.line,
+            }),
+            "while" => {
+                let (skip_true, _) = self.condition(token)?;
+                self.emit(skip_true);
+                let jump = self.placeholder();
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { exits, .. } => Some(exits),
+                    _ => None,
+                }) {
+                    Some(exits) => exits.push(jump),
+                    None => return Err(error(token.line, "`while` outside of a `loop`")),
+                }
+            }
+            "again" => {
+                let Some(Block::Loop { start, exits, .. }) = self.blocks.pop() else {
+                    return Err(error(token.line, "`again` without a matching `loop`"));
+                };
+                let jump = self.placeholder();
+                self.patch(jump, start as i32, token.line, false)?;
+                for exit in exits {
+                    self.patch(exit, self.address() as i32, token.line, false)?;
+                }
+            }
+            text if self.register_index(text).is_some() => self.register_statement(token)?,
+            text if self.value(text).is_some() => {
+                let value = self.byte(token)?;
+                self.output.push(value);
+            }
+            _ => self.emit_address(0x2000, token)?,
+        }
+
+        Ok(())
+    }
+
+    // Statements starting with a register, such as `v0 += v1`
+    fn register_statement(&mut self, register: Token<'a>) -> Result<(), ChipError> {
+        let x = (self.register_index(register.text).unwrap_or_default() as u16) << 8;
+        let operator = self.operand(register)?;
+        let operand = self.operand(operator)?;
+        let y = self.register_index(operand.text).map(|y| (y as u16) << 4);
+
+        let opcode = match (operator.text, y) {
+            (":=", Some(y)) => 0x8000 | x | y,
+            (":=", None) => match operand.text {
+                "random" => {
+                    let mask = self.operand(operand)?;
+                    0xC000 | x | self.byte(mask)? as u16
+                }
+                "delay" => 0xF007 | x,
+                "key" => 0xF00A | x,
+                _ => 0x6000 | x | self.byte(operand)? as u16,
+            },
+            ("+=", Some(y)) => 0x8004 | x | y,
+            ("+=", None) => 0x7000 | x | self.byte(operand)? as u16,
+            ("-=", Some(y)) => 0x8005 | x | y,
+            ("-=", None) => 0x7000 | x | self.byte(operand)?.wrapping_neg() as u16,
+            ("|=", Some(y)) => 0x8001 | x | y,
+            ("&=", Some(y)) => 0x8002 | x | y,
+            ("^=", Some(y)) => 0x8003 | x | y,
+            (">>=", Some(y)) => 0x8006 | x | y,
+            ("=-", Some(y)) => 0x8007 | x | y,
+            ("<<=", Some(y)) => 0x800E | x | y,
+            ("|=" | "&=" | "^=" | ">>=" | "=-" | "<<=", None) => {
+                return Err(unexpected(operand, "a register"));
+            }
+            _ => return Err(unexpected(operator, "an operator")),
+        };
+        self.emit(opcode);
+
+        Ok(())
+    }
+
+    // Statements assigning to the index register, such as `i := hex v0`
+    fn index_statement(&mut self, index: Token<'a>) -> Result<(), ChipError> {
+        let operator = self.operand(index)?;
+        match operator.text {
+            "+=" => self.emit_register(0xF01E, operator),
+            ":=" => {
+                let operand = self.operand(operator)?;
+                match operand.text {
+                    "hex" => self.emit_register(0xF029, operand),
+                    "bighex" => self.emit_register(0xF030, operand),
+                    "long" => {
+                        let target = self.operand(operand)?;
+                        self.emit(0xF000);
+                        self.emit(0x0000);
+                        self.reference(target, true)
+                    }
+                    _ => self.emit_address(0xA000, operand),
+                }
+            }
+            _ => Err(unexpected(operator, "`:=` or `+=`")),
+        }
+    }
+
+    // Parses `vx == operand`, `vx != operand`, `vx key` or `vx -key` and returns the
+    // instructions skipping when the condition is true and when it is false
+    fn condition(&mut self, previous: Token) -> Result<(u16, u16), ChipError> {
+        let x = (self.register(previous)? as u16) << 8;
+        let operator = self.operand(previous)?;
+
+        let (skip_equal, skip_not_equal) = match operator.text {
+            "key" => return Ok((0xE09E | x, 0xE0A1 | x)),
+            "-key" => return Ok((0xE0A1 | x, 0xE09E | x)),
+            "==" | "!=" => {
+                let operand = self.operand(operator)?;
+                match self.register_index(operand.text) {
+                    Some(y) => (0x5000 | x | (y as u16) << 4, 0x9000 | x | (y as u16) << 4),
+                    None => {
+                        let nn = self.byte(operand)? as u16;
+                        (0x3000 | x | nn, 0x4000 | x | nn)
+                    }
+                }
+            }
+            _ => return Err(unexpected(operator, "`==`, `!=`, `key` or `-key`")),
+        };
+
+        match operator.text {
+            "==" => Ok((skip_equal, skip_not_equal)),
+            _ => Ok((skip_not_equal, skip_equal)),
+        }
+    }
+
+    fn emit_register(&mut self, opcode: u16, previous: Token) -> Result<(), ChipError> {
+        let x = self.register(previous)?;
+        self.emit(opcode | (x as u16) << 8);
+
+        Ok(())
+    }
+
+    fn emit_address(&mut self, opcode: u16, target: Token<'a>) -> Result<(), ChipError> {
+        self.emit(opcode);
+        self.reference(target, false)
+    }
+
+    // Fills in the address held by the two bytes emitted last
+    fn reference(&mut self, target: Token<'a>, long: bool) -> Result<(), ChipError> {
+        let offset = self.output.len() - 2;
+
+        match self.value(target.text) {
+            Some(address) => self.patch(offset, address, target.line, long),
+            None if is_identifier(target.text) => {
+                self.fixups.push(Fixup {
+                    offset,
+                    label: target.text,
+                    line: target.line,
+                    long,
+                });
+                Ok(())
+            }
+            None => Err(unexpected(target, "an address or a label")),
+        }
+    }
+
+    // Emits a jump whose address is patched once the end of the block is known
+    fn placeholder(&mut self) -> usize {
+        self.emit(0x1000);
+
+        self.output.len() - 2
+    }
+
+    fn patch(
+        &mut self,
+        offset: usize,
+        address: i32,
+        line: usize,
+        long: bool,
+    ) -> Result<(), ChipError> {
+        let limit = if long { 0xFFFF } else { 0xFFF };
+        if !(0..=limit).contains(&address) {
+            return Err(error(
+                line,
+                format!("the address {address:#x} doesn't fit in {limit:#x}"),
+            ));
+        }
+
+        let bytes = match long {
+            true => (address as u16).to_be_bytes(),
+            false => {
+                let opcode = u16::from_be_bytes([self.output[offset], self.output[offset + 1]]);
+                (opcode | address as u16).to_be_bytes()
+            }
+        };
+        self.output[offset..offset + 2].copy_from_slice(&bytes);
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, ChipError> {
+        if let Some(block) = self.blocks.last() {
+            let (line, message) = match block {
+                Block::Loop { line, .. } => (*line, "`loop` without a matching `again`"),
+                Block::If { line, .. } => (*line, "`if ... begin` without a matching `end`"),
+                Block::Else { line, .. } => (*line, "`else` without a matching `end`"),
+            };
+            return Err(error(line, message));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let address = *self.labels.get(fixup.label).ok_or_else(|| {
+                error(
+                    fixup.line,
+                    format!("the label `{}` is not defined", fixup.label),
+                )
+            })?;
+            self.patch(fixup.offset, address as i32, fixup.line, fixup.long)?;
+        }
+
+        Ok(self.output)
+    }
+
+    fn identifier(&mut self, previous: Token) -> Result<Token<'a>, ChipError> {
+        let token = self.operand(previous)?;
+        if !is_identifier(token.text) || self.register_index(token.text).is_some() {
+            return Err(unexpected(token, "a name"));
+        }
+
+        Ok(token)
+    }
+
+    fn register(&mut self, previous: Token) -> Result<u8, ChipError> {
+        let token = self.operand(previous)?;
+
+        self.register_index(token.text)
+            .ok_or_else(|| unexpected(token, "a register"))
+    }
+
+    fn register_index(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+
+        match text.strip_prefix(['v', 'V']) {
+            Some(digit) if digit.len() == 1 => u8::from_str_radix(digit, 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn value(&self, text: &str) -> Option<i32> {
+        self.constants
+            .get(text)
+            .copied()
+            .or_else(|| parse_number(text))
+    }
+
+    fn byte(&self, token: Token) -> Result<u8, ChipError> {
+        match self.value(token.text) {
+            Some(value) if (-128..=255).contains(&value) => Ok(value as u8),
+            _ => Err(unexpected(token, "a byte")),
+        }
+    }
+
+    fn nibble(&mut self, previous: Token, limit: i32) -> Result<u16, ChipError> {
+        let token = self.operand(previous)?;
+        match self.value(token.text) {
+            Some(value) if (0..=limit).contains(&value) => Ok(value as u16),
+            _ => Err(unexpected(
+                token,
+                &format!("a number between 0 and {limit}"),
+            )),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn is_identifier(text: &str) -> bool {
+    parse_number(text).is_none()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn error(line: usize, message: impl Into<String>) -> ChipError {
+    ChipError::AssemblyError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn unexpected(token: Token, expected: &str) -> ChipError {
+    error(
+        token.line,
+        format!("expected {expected}, found `{}`", token.text),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disassemble, Chip8, Syntax};
+
+    #[test]
+    fn instructions() {
+        let source = "
+            clear
+            v1 := 0x2A   # Comment
+            v2 += -1
+            va ^= vb
+            i := hex v3
+            i := long 0x1234
+            sprite v0 v1 15
+            save v2 - v5
+            if v1 != 5 then return
+            0b10000001 255
+        ";
+        let rom = assemble(source, 0x200).unwrap();
+
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x61, 0x2A, 0x72, 0xFF, 0x8A, 0xB3, 0xF3, 0x29, 0xF0, 0x00, 0x12, 0x34,
+                0xD0, 0x1F, 0x52, 0x52, 0x31, 0x05, 0x00, 0xEE, 0x81, 0xFF
+            ]
+        );
+    }
+
+    #[test]
+    fn labels_and_control_flow() {
+        let source = "
+            :const LIMIT 10
+            :alias counter v0
+            : main
+                i := sprite
+                loop
+                    counter += 1
+                    add-three
+                    while counter != LIMIT
+                again
+                if v1 == 30 begin
+                    v2 := 1
+                else
+                    v2 := 2
+                end
+                i := sprite
+                load v0
+            : halt
+                jump halt
+            : add-three
+                v1 += 3
+                ;
+            : sprite
+                0xF0 0x90
+        ";
+        let rom = assemble(source, 0x200).unwrap();
+
+        let mut c8 = Chip8::default();
+        c8.load_rom(&rom).unwrap();
+        for _ in 0..100 {
+            c8.step().unwrap();
+        }
+        assert_eq!(c8.cpu.v[0], 0xF0);
+        assert_eq!(c8.cpu.v[1], 30);
+        assert_eq!(c8.cpu.v[2], 1);
+        assert_eq!(&rom[rom.len() - 2..], [0xF0, 0x90]);
+    }
+
+    #[test]
+    fn errors() {
+        let e = assemble("clear\n\nv0 := 0x100", 0x200);
+        assert!(matches!(e, Err(ChipError::AssemblyError { line: 3, .. })));
+
+        let e = assemble("v0 := 1\njump nowhere", 0x200);
+        assert!(matches!(e, Err(ChipError::AssemblyError { line: 2, .. })));
+
+        let e = assemble("loop\n  v0 += 1\n", 0x200);
+        assert!(matches!(e, Err(ChipError::AssemblyError { line: 1, .. })));
+
+        let e = assemble(": start\n: start", 0x200);
+        assert!(matches!(e, Err(ChipError::AssemblyError { line: 2, .. })));
+
+        let e = assemble("sprite v0 v1", 0x200);
+        assert!(matches!(e, Err(ChipError::AssemblyError { line: 1, .. })));
+    }
+
+    #[test]
+    fn disassembly_round_trip() {
+        let rom = [
+            0x22, 0x08, 0x3A, 0x07, 0x8A, 0xB7, 0xEA, 0x9E, 0xF0, 0x00, 0xAB, 0xCD, 0xCE, 0x0F,
+            0xF3, 0x01, 0x00, 0xC3,
+        ];
+        let source: Vec<String> = disassemble(&rom, 0x200)
+            .iter()
+            .map(|line| line.instruction.mnemonic(Syntax::Octo))
+            .collect();
+
+        assert_eq!(assemble(&source.join("\n"), 0x200).unwrap(), rom);
+    }
+}
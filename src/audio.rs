@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use crate::Chip8;
+
+// Sound changes kept while nobody renders audio, older ones are applied right away
+const MAX_EVENTS: usize = 1024;
+// The XO-CHIP plays the pattern at this many bits per second at the default pitch of 64
+const PATTERN_BASE_RATE: f32 = 4000.0;
+const PATTERN_BITS: f32 = 128.0;
+
+/// The shape of the tone played while the sound timer is active.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    /// The harsh beep of most interpreters.
+    #[default]
+    Square,
+    /// A softer tone between a square and a sine wave.
+    Triangle,
+    /// A bright, buzzing tone.
+    Sawtooth,
+    /// A pure tone.
+    Sine,
+}
+
+/// Renders the tone of the sound timer as PCM samples.
+///
+/// The moments the sound timer was set and expired are recorded in emulated time, so
+/// the tone starts and stops at the exact sample no matter how many instructions run
+/// between two calls to [`Chip8::render_audio`].
+///
+/// With the XO-CHIP variant, the 128 bit audio pattern loaded by F002 is played instead of
+/// the tone, one bit after another at 4000 * 2 ^ ((pitch - 64) / 48) bits per second where
+/// the pitch is set by FX3A. Until a pattern is loaded the tone is played.
+#[derive(Clone)]
+pub struct Audio {
+    /// The shape of the tone.
+    pub waveform: Waveform,
+    /// The frequency of the tone in Hz.
+    pub frequency: f32,
+    /// The loudness of the tone, from 0.0 to 1.0.
+    pub volume: f32,
+    /// How long the tone takes to fade in and out, which avoids clicks.
+    pub ramp: Duration,
+    // Changes of the sound not rendered yet, at the emulated time in seconds
+    events: VecDeque<(f64, Sound)>,
+    // The sound when the last change was recorded
+    latest: Sound,
+    playing: Sound,
+    // The emulated time in seconds up to which samples were rendered
+    rendered: f64,
+    phase: f32,
+    // The bit of the XO-CHIP audio pattern being played
+    position: f32,
+    gain: f32,
+}
+
+// What the machine is playing
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct Sound {
+    playing: bool,
+    // The XO-CHIP audio pattern and pitch
+    pattern: Option<([u8; 16], u8)>,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Audio {
+            waveform: Waveform::default(),
+            frequency: 440.0,
+            volume: 0.25,
+            ramp: Duration::from_millis(5),
+            events: VecDeque::new(),
+            latest: Sound::default(),
+            playing: Sound::default(),
+            rendered: 0.0,
+            phase: 0.0,
+            position: 0.0,
+            gain: 0.0,
+        }
+    }
+}
+
+impl Audio {
+    /// Record a change of the sound at the emulated time in seconds.
+    fn update(&mut self, sound: Sound, time: f64) {
+        if sound == self.latest {
+            return;
+        }
+
+        self.latest = sound;
+        self.events.push_back((time, sound));
+        if self.events.len() > MAX_EVENTS {
+            self.apply_next_event();
+        }
+    }
+
+    /// Stop the tone and forget every recorded change, keeping the settings.
+    pub(crate) fn reset(&mut self) {
+        *self = Audio {
+            waveform: self.waveform,
+            frequency: self.frequency,
+            volume: self.volume,
+            ramp: self.ramp,
+            ..Default::default()
+        };
+    }
+
+    fn apply_next_event(&mut self) {
+        if let Some((_, sound)) = self.events.pop_front() {
+            // Every beep starts at the first bit of the pattern
+            if sound.playing && !self.playing.playing {
+                self.position = 0.0;
+            }
+            self.playing = sound;
+        }
+    }
+
+    fn render(&mut self, sample_rate: u32, buffer: &mut [f32]) {
+        let rate = sample_rate as f32;
+        let ramp_step = 1.0 / (self.ramp.as_secs_f32() * rate).max(1.0);
+
+        for (index, sample) in buffer.iter_mut().enumerate() {
+            let time = self.rendered + index as f64 / sample_rate as f64;
+            while matches!(self.events.front(), Some(&(at, _)) if at <= time) {
+                self.apply_next_event();
+            }
+
+            let target = if self.playing.playing { 1.0 } else { 0.0 };
+            self.gain = match self.gain < target {
+                true => (self.gain + ramp_step).min(target),
+                false => (self.gain - ramp_step).max(target),
+            };
+
+            match self.playing.pattern {
+                Some((pattern, pitch)) => {
+                    let bit = self.position as usize;
+                    let high = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                    *sample = if high { 1.0 } else { -1.0 } * self.volume * self.gain;
+                    self.position = (self.position + pattern_rate(pitch) / rate) % PATTERN_BITS;
+                }
+                None => {
+                    *sample = self.wave() * self.volume * self.gain;
+                    self.phase = (self.phase + self.frequency / rate).fract();
+                }
+            }
+        }
+
+        self.rendered += buffer.len() as f64 / sample_rate as f64;
+    }
+
+    fn wave(&self) -> f32 {
+        match self.waveform {
+            Waveform::Square if self.phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * self.phase - 1.0,
+            Waveform::Sine => (TAU * self.phase).sin(),
+        }
+    }
+}
+
+// The bits per second the XO-CHIP plays the audio pattern at
+fn pattern_rate(pitch: u8) -> f32 {
+    PATTERN_BASE_RATE * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+impl Chip8 {
+    /// Fill the buffer with mono samples of the sound timer tone at the sample rate.
+    ///
+    /// Every call continues where the previous one stopped, so the buffers should cover as
+    /// much time as the emulation ran, such as one 60th of a second per [`Chip8::tick`].
+    /// The tone is configured through [`Chip8::audio`].
+    pub fn render_audio(&mut self, sample_rate: u32, buffer: &mut [f32]) {
+        self.audio.render(sample_rate, buffer);
+    }
+
+    pub(crate) fn record_sound(&mut self) {
+        let sound = Sound {
+            playing: self.cpu.timer_sound > 0,
+            pattern: (self.config.variant.has_xo_chip() && self.cpu.pattern_loaded)
+                .then_some((self.cpu.audio_pattern, self.cpu.pitch)),
+        };
+        let time = self.emulated_seconds();
+        self.audio.update(sound, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn render() {
+        let mut c8 = Chip8::new(Config {
+            tick_rate: 10,
+            ..Default::default()
+        });
+        // Play a tone for 2 frames starting at the 5th instruction
+        c8.load_rom(&[
+            0x60, 0x02, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0xE0, 0xF0, 0x18, 0x12, 0x0A,
+        ])
+        .unwrap();
+        c8.audio.ramp = Duration::ZERO;
+        c8.audio.volume = 1.0;
+
+        let mut buffer = [0.0; 600];
+        for _ in 0..3 {
+            c8.tick().unwrap();
+        }
+        c8.render_audio(12000, &mut buffer);
+
+        // 12000 Hz gives 20 samples per instruction, FX18 ends at 100 and the timer
+        // expires at the end of the second frame at 400
+        assert!(buffer[..100].iter().all(|&sample| sample == 0.0));
+        assert!(buffer[100..400].iter().all(|&sample| sample.abs() == 1.0));
+        assert!(buffer[400..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn ramp() {
+        let mut audio = Audio {
+            waveform: Waveform::Square,
+            frequency: 1.0,
+            volume: 1.0,
+            ramp: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let sound = Sound {
+            playing: true,
+            pattern: None,
+        };
+        audio.update(sound, 0.0);
+        audio.update(Sound::default(), 0.02);
+
+        let mut buffer = [0.0; 40];
+        audio.render(1000, &mut buffer);
+        assert!((buffer[4] - 0.5).abs() < 1e-5);
+        assert_eq!(buffer[15], 1.0);
+        assert!((buffer[24] - 0.5).abs() < 1e-5);
+        assert_eq!(buffer[35], 0.0);
+    }
+
+    #[test]
+    fn xo_chip_pattern() {
+        let mut c8 = Chip8::new(Config {
+            tick_rate: 10,
+            ..Config::xo_chip()
+        });
+        // Play the pattern at 0x20C at twice the default rate
+        let rom = [
+            0xA2, 0x0C, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A, 0xF0, 0x18, 0x12, 0x0A,
+        ];
+        c8.load_rom(&rom).unwrap();
+        c8.memory[0x20C..0x21C].copy_from_slice(&[0xF0; 16]);
+        c8.audio.ramp = Duration::ZERO;
+        c8.audio.volume = 1.0;
+
+        c8.tick().unwrap();
+        c8.tick().unwrap();
+        let mut buffer = [0.0; 400];
+        c8.render_audio(24000, &mut buffer);
+
+        // 40 samples per instruction and 3 samples per bit, leaving out the samples at the
+        // edges that depend on rounding
+        assert_eq!(pattern_rate(0x70), 8000.0);
+        assert!(buffer[..199].iter().all(|&sample| sample == 0.0));
+        assert!(buffer[201..211].iter().all(|&sample| sample == 1.0));
+        assert!(buffer[213..223].iter().all(|&sample| sample == -1.0));
+        assert!(buffer[225..235].iter().all(|&sample| sample == 1.0));
+    }
+
+    #[test]
+    fn xo_chip_silent_pattern() {
+        let mut c8 = Chip8::new(Config {
+            tick_rate: 10,
+            ..Config::xo_chip()
+        });
+        // Play the all zero pattern at 0x208 instead of the default tone
+        let rom = [0xA2, 0x08, 0xF0, 0x02, 0xF0, 0x18, 0x12, 0x06];
+        c8.load_rom(&rom).unwrap();
+        c8.cpu.v[0] = 0x20;
+        c8.audio.ramp = Duration::ZERO;
+        c8.audio.volume = 1.0;
+
+        c8.tick().unwrap();
+        c8.tick().unwrap();
+        let mut buffer = [0.0; 400];
+        c8.render_audio(24000, &mut buffer);
+        // The pattern holds the output low instead of playing the default square wave
+        assert!(c8.cpu.pattern_loaded);
+        assert!(buffer[..119].iter().all(|&sample| sample == 0.0));
+        assert!(buffer[121..].iter().all(|&sample| sample == -1.0));
+
+        c8.reset();
+        assert!(!c8.cpu.pattern_loaded);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::errors::ChipError;
+
+/// The address space the [Cpu](crate::Cpu) fetches its instructions from and accesses
+/// data through.
+///
+/// Implement it to map peripherals into the memory, make regions read-only or watch the
+/// accesses of a program. A plain `[u8]` array, slice or `Vec<u8>` acts as RAM, with the
+/// accesses past its end failing with [`ChipError::AddressOutOfBounds`].
+pub trait Bus {
+    /// The size of the address space in bytes.
+    fn size(&self) -> usize;
+
+    /// Read the byte at the address.
+    fn read(&self, address: usize) -> Result<u8, ChipError>;
+
+    /// Write a byte to the address.
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ChipError>;
+
+    /// Read the big endian instruction starting at the address.
+    fn fetch(&self, address: usize) -> Result<u16, ChipError> {
+        Ok(u16::from_be_bytes([
+            self.read(address)?,
+            self.read(address + 1)?,
+        ]))
+    }
+}
+
+impl Bus for [u8] {
+    #[inline]
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn read(&self, address: usize) -> Result<u8, ChipError> {
+        self.get(address)
+            .copied()
+            .ok_or(ChipError::AddressOutOfBounds {
+                address,
+                limit: self.len(),
+            })
+    }
+
+    #[inline]
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ChipError> {
+        let limit = self.len();
+        let byte = self
+            .get_mut(address)
+            .ok_or(ChipError::AddressOutOfBounds { address, limit })?;
+        *byte = value;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn fetch(&self, address: usize) -> Result<u16, ChipError> {
+        match self.get(address..address + 2) {
+            Some(&[hi, lo]) => Ok(u16::from_be_bytes([hi, lo])),
+            _ => Err(ChipError::AddressOutOfBounds {
+                address: address + 1,
+                limit: self.len(),
+            }),
+        }
+    }
+}
+
+impl<const N: usize> Bus for [u8; N] {
+    #[inline]
+    fn size(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn read(&self, address: usize) -> Result<u8, ChipError> {
+        self.as_slice().read(address)
+    }
+
+    #[inline]
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ChipError> {
+        self.as_mut_slice().write(address, value)
+    }
+
+    #[inline]
+    fn fetch(&self, address: usize) -> Result<u16, ChipError> {
+        self.as_slice().fetch(address)
+    }
+}
+
+impl Bus for Vec<u8> {
+    #[inline]
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn read(&self, address: usize) -> Result<u8, ChipError> {
+        self.as_slice().read(address)
+    }
+
+    #[inline]
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ChipError> {
+        self.as_mut_slice().write(address, value)
+    }
+
+    #[inline]
+    fn fetch(&self, address: usize) -> Result<u16, ChipError> {
+        self.as_slice().fetch(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8, Config, Cpu, Screen};
+
+    // Memory whose lower half is read-only, recording the written addresses
+    struct Rom {
+        memory: [u8; 0x20],
+        writes: Vec<usize>,
+    }
+
+    impl Bus for Rom {
+        fn size(&self) -> usize {
+            self.memory.len()
+        }
+
+        fn read(&self, address: usize) -> Result<u8, ChipError> {
+            self.memory.read(address)
+        }
+
+        fn write(&mut self, address: usize, value: u8) -> Result<(), ChipError> {
+            if address < 0x10 {
+                return Err(ChipError::AddressOutOfBounds {
+                    address,
+                    limit: self.memory.len(),
+                });
+            }
+            self.writes.push(address);
+            self.memory.write(address, value)
+        }
+    }
+
+    #[test]
+    fn flat_memory() {
+        let mut memory = [0x12, 0x34, 0x56];
+
+        assert_eq!(memory.read(2).unwrap(), 0x56);
+        assert_eq!(memory.fetch(0).unwrap(), 0x1234);
+        memory.write(1, 0x78).unwrap();
+        assert_eq!(memory.as_slice().fetch(1).unwrap(), 0x7856);
+
+        assert!(matches!(
+            memory.read(3),
+            Err(ChipError::AddressOutOfBounds {
+                address: 3,
+                limit: 3
+            })
+        ));
+        assert!(matches!(
+            vec![0; 3].write(3, 0),
+            Err(ChipError::AddressOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            memory.fetch(2),
+            Err(ChipError::AddressOutOfBounds {
+                address: 3,
+                limit: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn custom_bus() {
+        let mut cpu = Cpu::default();
+        let mut screen = Screen::default();
+        let config = Config::default();
+        let mut rom = Rom {
+            memory: [0; 0x20],
+            writes: Vec::new(),
+        };
+        // V0 = 0x2A, I = 0x10, store V0 then I = 0x00, store V0
+        rom.memory[..10]
+            .copy_from_slice(&[0x60, 0x2A, 0xA0, 0x10, 0xF0, 0x55, 0xA0, 0x00, 0xF0, 0x55]);
+
+        for _ in 0..3 {
+            cpu.step(&mut rom, &mut screen, &config).unwrap();
+        }
+        assert_eq!(rom.memory[0x10], 0x2A);
+        assert_eq!(rom.writes, [0x10]);
+
+        cpu.step(&mut rom, &mut screen, &config).unwrap();
+        let e = cpu.step(&mut rom, &mut screen, &config).unwrap_err();
+        assert!(matches!(
+            e.root(),
+            ChipError::AddressOutOfBounds { address: 0, .. }
+        ));
+        assert_eq!(rom.memory[0], 0x60);
+    }
+
+    #[test]
+    fn chip8_custom_bus() {
+        let mut c8 = Chip8::default();
+        let mut rom = Rom {
+            memory: [0; 0x20],
+            writes: Vec::new(),
+        };
+        // V0 = 0x2A, I = 0x10, store V0, sound timer = V0
+        rom.memory[..8].copy_from_slice(&[0x60, 0x2A, 0xA0, 0x10, 0xF0, 0x55, 0xF0, 0x18]);
+
+        c8.cpu.pc = 0;
+        for _ in 0..4 {
+            c8.step_with(&mut rom).unwrap();
+        }
+        assert_eq!(rom.writes, [0x10]);
+        assert_eq!(c8.memory[0x10], 0);
+        assert!(c8.should_play_sound());
+        assert_eq!(c8.emulated_seconds(), 4.0 / 600.0);
+    }
+}
@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use crate::bus::Bus;
+use crate::debugger::MemoryAccesses;
+use crate::errors::ChipError;
+use crate::timing::{
+    machine_cycles, INTERPRETER_CYCLES_PER_FRAME, INTERRUPT_CYCLES, MACHINE_CYCLES_PER_SECOND,
+};
+use crate::{Chip8, Timing};
+
+const TIMER_HZ: u128 = 60;
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Carries the progress of [`Chip8::run_for`] and [`Chip8::run_cycles`] between calls.
+///
+/// Time is measured in instructions with [`Timing::TickRate`] and in 1802 machine cycles
+/// with [`Timing::CosmacVip`].
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Clock {
+    // Time spent since the machine was reset
+    pub cycles: u64,
+    // Time spent since the timers were last ticked
+    frame_cycles: u32,
+    // Time not yet turned into a whole cycle, in nanoseconds times the frequency
+    remainder: u128,
+    // Cycles that can be spent, negative when the last instruction took more than was left
+    credit: i64,
+}
+
+impl Clock {
+    pub fn reset(&mut self) {
+        *self = Clock::default();
+    }
+}
+
+impl Chip8 {
+    /// Run the machine for an amount of real time, such as the time since the last frame
+    /// was rendered.
+    ///
+    /// The CPU runs at [`Config::tick_rate`](crate::Config::tick_rate) instructions per
+    /// 60th of a second, or at the speed of the COSMAC VIP with [`Timing::CosmacVip`], and
+    /// the timers tick at 60 Hz, no matter how often this is called. Time that doesn't add
+    /// up to a whole instruction is carried over to the next call. Returns the amount of
+    /// instructions executed, none with a tick rate of 0, where only the timers run.
+    pub fn run_for(&mut self, duration: Duration) -> Result<u64, ChipError> {
+        if self.timers_only() {
+            self.clock.remainder += duration.as_nanos() * TIMER_HZ;
+            for _ in 0..self.clock.remainder / NANOS_PER_SECOND {
+                self.run_frame()?;
+            }
+            self.clock.remainder %= NANOS_PER_SECOND;
+
+            return Ok(0);
+        }
+
+        self.clock.remainder += duration.as_nanos() * self.frequency();
+        self.clock.credit += (self.clock.remainder / NANOS_PER_SECOND) as i64;
+        self.clock.remainder %= NANOS_PER_SECOND;
+
+        let mut instructions = 0;
+        while self.clock.credit > 0 {
+            let (cycles, _) = self.advance()?;
+            self.clock.credit -= cycles as i64;
+            instructions += 1;
+        }
+
+        Ok(instructions)
+    }
+
+    /// Execute an amount of instructions, ticking the timers every
+    /// [`Config::tick_rate`](crate::Config::tick_rate) instructions, or every frame of the
+    /// COSMAC VIP with [`Timing::CosmacVip`].
+    ///
+    /// A partial frame is carried over to the next call, so running 1 cycle at a time ticks
+    /// the timers just as often as running a whole frame at once.
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), ChipError> {
+        for _ in 0..cycles {
+            self.advance()?;
+        }
+
+        Ok(())
+    }
+
+    // The emulated time since the machine was reset
+    pub(crate) fn emulated_seconds(&self) -> f64 {
+        self.clock.cycles as f64 / self.frequency() as f64
+    }
+
+    // How many cycles of the timing run every second
+    fn frequency(&self) -> u128 {
+        match self.config.timing {
+            // Only the frames are counted
+            Timing::TickRate if self.timers_only() => TIMER_HZ,
+            Timing::TickRate => self.config.tick_rate as u128 * TIMER_HZ,
+            Timing::CosmacVip => MACHINE_CYCLES_PER_SECOND,
+        }
+    }
+
+    // Runs the machine until the end of the current frame
+    pub(crate) fn run_frame(&mut self) -> Result<(), ChipError> {
+        if self.timers_only() {
+            self.clock.cycles += 1;
+            self.end_frame();
+            return Ok(());
+        }
+
+        while !self.advance()?.1 {}
+
+        Ok(())
+    }
+
+    // Without any instructions per frame only the timers run
+    fn timers_only(&self) -> bool {
+        self.config.timing == Timing::TickRate && self.config.tick_rate == 0
+    }
+
+    // Executes an instruction and returns the time it took and whether the frame ended
+    fn advance(&mut self) -> Result<(u32, bool), ChipError> {
+        self.advance_with(None)
+    }
+
+    // Same as advance, with the memory accessed by the instruction recorded for the debugger
+    pub(crate) fn advance_with(
+        &mut self,
+        accesses: Option<&mut MemoryAccesses>,
+    ) -> Result<(u32, bool), ChipError> {
+        let step = |chip: &mut Chip8| match accesses {
+            Some(accesses) => chip.step_recorded(accesses),
+            None => chip.step(),
+        };
+
+        // Chip8::step counts the instructions itself
+        if self.config.timing == Timing::TickRate {
+            step(self)?;
+            self.clock.frame_cycles += 1;
+            let frame_ended = self.clock.frame_cycles >= self.config.tick_rate;
+            if frame_ended {
+                self.clock.frame_cycles = 0;
+                self.end_frame();
+            }
+
+            return Ok((1, frame_ended));
+        }
+
+        let (pc, v) = (self.cpu.pc, self.cpu.v);
+        let opcode = self.memory.fetch(pc).unwrap_or(0);
+        let idle = self.cpu.halted || self.cpu.waiting_vblank;
+        step(self)?;
+
+        // A CPU waiting for the display sleeps until the interrupt
+        let mut cycles = match idle {
+            true => INTERPRETER_CYCLES_PER_FRAME - self.clock.frame_cycles,
+            false => {
+                let skipped =
+                    matches!(opcode >> 12, 0x3 | 0x4 | 0x5 | 0x9 | 0xE) && self.cpu.pc > pc + 2;
+                machine_cycles(opcode, &v, skipped)
+            }
+        };
+
+        self.clock.frame_cycles += cycles;
+        self.clock.cycles += cycles as u64;
+        let frame_ended = self.clock.frame_cycles >= INTERPRETER_CYCLES_PER_FRAME;
+        if frame_ended {
+            self.clock.frame_cycles -= INTERPRETER_CYCLES_PER_FRAME;
+            self.end_frame();
+            cycles += INTERRUPT_CYCLES;
+            self.clock.cycles += INTERRUPT_CYCLES as u64;
+        }
+
+        Ok((cycles, frame_ended))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    fn test_setup() -> Chip8 {
+        let mut c8 = Chip8::new(Config {
+            tick_rate: 10,
+            ..Default::default()
+        });
+        // Increment V0 forever
+        c8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        c8.cpu.timer_delay = 100;
+
+        c8
+    }
+
+    #[test]
+    fn run_for() {
+        let mut c8 = test_setup();
+
+        // 144 Hz frames for one second
+        let cycles: u64 = (0..144)
+            .map(|_| c8.run_for(Duration::from_secs(1) / 144).unwrap())
+            .sum();
+        assert!((599..=600).contains(&cycles));
+        assert_eq!(c8.cpu.timer_delay, 100 - (cycles / 10) as u8);
+
+        // 600 Hz runs an instruction every 1.67 ms
+        let mut c8 = test_setup();
+        assert_eq!(c8.run_for(Duration::from_millis(1)).unwrap(), 0);
+        assert_eq!(c8.run_for(Duration::from_millis(1)).unwrap(), 1);
+        assert_eq!(c8.run_for(Duration::from_millis(1)).unwrap(), 0);
+        assert_eq!(c8.run_for(Duration::from_millis(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn run_cycles() {
+        let mut c8 = test_setup();
+
+        for _ in 0..25 {
+            c8.run_cycles(1).unwrap();
+        }
+        assert_eq!(c8.cpu.timer_delay, 98);
+        c8.run_cycles(5).unwrap();
+        assert_eq!(c8.cpu.timer_delay, 97);
+        assert_eq!(c8.cpu.v[0], 15);
+    }
+
+    #[test]
+    fn cosmac_vip_timing() {
+        let mut c8 = Chip8::new(Config {
+            timing: Timing::CosmacVip,
+            ..Config::cosmac_vip()
+        });
+        // Increment V0 forever, each loop takes 50 + 52 machine cycles, leaving room for
+        // (220112 - 60 * 1072) / 51 instructions per second
+        c8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        c8.cpu.timer_delay = 100;
+
+        assert_eq!(c8.run_for(Duration::from_secs(1)).unwrap(), 3055);
+        assert_eq!(c8.cpu.timer_delay, 40);
+
+        c8.tick().unwrap();
+        assert_eq!(c8.cpu.timer_delay, 39);
+
+        // Drawing waits for the interrupt
+        c8.load_rom(&[0xD0, 0x01, 0x12, 0x00]).unwrap();
+        c8.cpu.pc = 0x200;
+        c8.run_cycles(2).unwrap();
+        assert_eq!(c8.cpu.timer_delay, 38);
+    }
+
+    #[test]
+    fn key_events() {
+        let mut c8 = test_setup();
+        // Wait for a key at 0x202 and loop at 0x204 after it
+        c8.load_rom(&[0x12, 0x00, 0xF1, 0x0A, 0x12, 0x04]).unwrap();
+        c8.cpu.pc = 0x202;
+
+        // A tap between two frames is seen by FX0A
+        c8.key_down(0x3).unwrap();
+        c8.key_up(0x3).unwrap();
+        c8.tick().unwrap();
+        assert_eq!(c8.cpu.pc, 0x204);
+        assert_eq!(c8.cpu.v[0x1], 0x3);
+
+        // Taps are forgotten at the end of the frame
+        c8.cpu.pc = 0x200;
+        c8.key_down(0x7).unwrap();
+        c8.key_up(0x7).unwrap();
+        c8.tick().unwrap();
+        c8.cpu.pc = 0x202;
+        c8.tick().unwrap();
+        assert_eq!(c8.cpu.pc, 0x202);
+
+        // A held key resumes FX0A once released
+        c8.key_down(0x9).unwrap();
+        c8.tick().unwrap();
+        assert_eq!(c8.cpu.pc, 0x202);
+        c8.key_up(0x9).unwrap();
+        c8.tick().unwrap();
+        assert_eq!(c8.cpu.v[0x1], 0x9);
+
+        assert!(matches!(
+            c8.key_down(0x10),
+            Err(ChipError::InvalidKey(0x10))
+        ));
+        assert!(matches!(c8.key_up(0xFF), Err(ChipError::InvalidKey(0xFF))));
+    }
+
+    #[test]
+    fn timers_only() {
+        let mut c8 = test_setup();
+        c8.config.tick_rate = 0;
+
+        // The timers tick at 60 Hz without running any instruction
+        assert_eq!(c8.run_for(Duration::from_secs(1)).unwrap(), 0);
+        assert_eq!(c8.cpu.timer_delay, 40);
+        assert_eq!(c8.cpu.v[0x0], 0);
+        assert_eq!(c8.run_for(Duration::from_millis(10)).unwrap(), 0);
+        assert_eq!(c8.run_for(Duration::from_millis(10)).unwrap(), 0);
+        assert_eq!(c8.cpu.timer_delay, 39);
+
+        c8.tick().unwrap();
+        assert_eq!(c8.cpu.timer_delay, 38);
+        assert_eq!(c8.emulated_seconds(), 62.0 / 60.0);
+    }
+}
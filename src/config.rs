@@ -1,9 +1,239 @@
+/// The memory size of the CHIP-8 and Super-Chip.
+pub const CHIP8_MEMORY_SIZE: usize = 4096;
+/// The memory size of the XO-CHIP.
+pub const XO_CHIP_MEMORY_SIZE: usize = 65536;
+
+/// The instruction set extensions understood by the interpreter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// The original CHIP-8 instruction set.
+    Chip8,
+    /// CHIP-8 plus the Super-Chip 1.1 instructions and the 128x64 display.
+    #[default]
+    SuperChip,
+    /// Super-Chip plus the XO-CHIP instructions, bitplanes and 64 KiB of memory.
+    XoChip,
+}
+
+impl Variant {
+    /// Returns true if the Super-Chip instructions are available.
+    pub fn has_schip(&self) -> bool {
+        matches!(self, Variant::SuperChip | Variant::XoChip)
+    }
+
+    /// Returns true if the XO-CHIP instructions are available.
+    pub fn has_xo_chip(&self) -> bool {
+        matches!(self, Variant::XoChip)
+    }
+}
+
+/// The built-in hexadecimal font loaded into memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Font {
+    /// The font of the original COSMAC VIP interpreter.
+    Cosmac,
+    /// The font of the CHIP-48 and Super-Chip interpreters.
+    #[default]
+    SuperChip,
+}
+
+/// How FX55 and FX65 change the I register after accessing memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    /// I is left unchanged (CHIP-48 and Super-Chip 1.1).
+    #[default]
+    None,
+    /// I is incremented by X (Super-Chip 1.0).
+    X,
+    /// I is incremented by X + 1 (COSMAC VIP and XO-CHIP).
+    XPlusOne,
+}
+
+/// What happens when an instruction accesses memory past the end through the I register.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryAccess {
+    /// The instruction fails with [`ChipError::AddressOutOfBounds`](crate::ChipError).
+    #[default]
+    Error,
+    /// The address wraps around to the start of the memory.
+    Wrap,
+}
+
+/// What happens when the CPU runs into an opcode it doesn't know, or one that isn't
+/// supported by the selected [Variant].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownOpcode {
+    /// The opcodes starting with 0 are skipped, which covers the 0NNN machine code calls
+    /// the interpreter can't run. Any other unknown opcode fails like with
+    /// [`UnknownOpcode::Error`].
+    #[default]
+    SkipMachineCode,
+    /// The step fails with [`ChipError::OpcodeNotImplemented`](crate::ChipError).
+    Error,
+    /// The opcode is skipped as if it was a no-op.
+    Skip,
+    /// The CPU halts like after the Super-Chip 00FD exit instruction.
+    Halt,
+    /// The handler set with
+    /// [`Cpu::set_unknown_opcode_handler`](crate::Cpu::set_unknown_opcode_handler)
+    /// executes the opcode. Without a handler it is treated like [`UnknownOpcode::Error`].
+    Trap,
+}
+
+/// How the speed of the CPU is measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// Every instruction takes the same time, [`Config::tick_rate`] of them run per frame.
+    #[default]
+    TickRate,
+    /// Every instruction takes the 1802 machine cycles it took in the COSMAC VIP
+    /// interpreter and the timers tick at the vertical blank interrupt, every 3668
+    /// machine cycles. [`Config::tick_rate`] is ignored.
+    CosmacVip,
+}
+
+/// Switches for the instructions that behave differently between CHIP-8 implementations.
+///
+/// The defaults match the behaviour of the CHIP-48 and Super-Chip interpreters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY and store the result in VX, instead of shifting VX in place.
+    pub shift_vy: bool,
+    /// How FX55 and FX65 increment the I register.
+    pub memory_increment: MemoryIncrement,
+    /// BNNN jumps to XNN + VX instead of NNN + V0.
+    pub jump_with_vx: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to zero.
+    pub vf_reset: bool,
+    /// Sprites are clipped at the edges of the screen instead of wrapping around.
+    pub clip_sprites: bool,
+    /// DXYN waits for the next frame, limiting the interpreter to one sprite draw per frame.
+    pub display_wait: bool,
+    /// FX0A resumes as soon as a key is pressed, instead of waiting for the key to be
+    /// released like the original interpreters.
+    pub key_press_wait: bool,
+    /// In hires mode DXYN sets VF to the number of sprite rows that collided or got
+    /// clipped at the bottom of the screen, instead of 1 for any collision (Super-Chip 1.1).
+    pub collision_rows: bool,
+}
+
 /// Settings to modify the behaviour of the interpreter.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Config {
     /// The location in memory where the loaded ROM data starts.
     pub rom_base_addr: usize,
     /// How many CPU cycles occur before every frame render cycle.
     pub tick_rate: u32,
+    /// How long every instruction takes.
+    pub timing: Timing,
+    /// The size of the memory in bytes. CHIP-8 uses 4 KiB while XO-CHIP uses 64 KiB.
+    pub memory_size: usize,
+    /// Which instruction set extensions are enabled.
+    pub variant: Variant,
+    /// Behaviour of the ambiguous instructions.
+    pub quirks: Quirks,
+    /// The font loaded into memory when the interpreter is created or reset.
+    pub font: Font,
+    /// How many frames are recorded for [`Chip8::rewind`](crate::Chip8::rewind).
+    /// Rewinding is disabled when set to zero.
+    pub rewind_depth: usize,
+    /// The seed of the random number generator used by CXNN. The same seed makes every
+    /// run identical. A random seed is picked when None.
+    pub seed: Option<u64>,
+    /// How instructions handle addresses past the end of the memory.
+    pub memory_access: MemoryAccess,
+    /// How the CPU handles unknown opcodes, including the 0NNN machine code calls.
+    pub unknown_opcode: UnknownOpcode,
+}
+
+impl Config {
+    /// Settings matching the original CHIP-8 interpreter on the COSMAC VIP.
+    pub fn cosmac_vip() -> Self {
+        Config {
+            tick_rate: 15,
+            variant: Variant::Chip8,
+            quirks: Quirks {
+                shift_vy: true,
+                memory_increment: MemoryIncrement::XPlusOne,
+                jump_with_vx: false,
+                vf_reset: true,
+                clip_sprites: true,
+                display_wait: true,
+                key_press_wait: false,
+                collision_rows: false,
+            },
+            font: Font::Cosmac,
+            ..Default::default()
+        }
+    }
+
+    /// Settings matching the CHIP-48 interpreter on the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Config {
+            tick_rate: 30,
+            variant: Variant::Chip8,
+            quirks: Quirks {
+                memory_increment: MemoryIncrement::X,
+                jump_with_vx: true,
+                clip_sprites: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Settings matching the Super-Chip 1.0 interpreter.
+    pub fn schip_1_0() -> Self {
+        Config {
+            quirks: Quirks {
+                memory_increment: MemoryIncrement::X,
+                ..Config::schip_modern().quirks
+            },
+            ..Config::schip_modern()
+        }
+    }
+
+    /// Settings matching the Super-Chip 1.1 interpreter, which waits for the next frame
+    /// before drawing.
+    pub fn schip_1_1() -> Self {
+        Config {
+            quirks: Quirks {
+                display_wait: true,
+                collision_rows: true,
+                ..Config::schip_modern().quirks
+            },
+            ..Config::schip_modern()
+        }
+    }
+
+    /// Settings matching how modern interpreters such as Octo run Super-Chip programs.
+    pub fn schip_modern() -> Self {
+        Config {
+            tick_rate: 30,
+            variant: Variant::SuperChip,
+            quirks: Quirks {
+                jump_with_vx: true,
+                clip_sprites: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Settings matching the XO-CHIP extension as implemented by Octo.
+    pub fn xo_chip() -> Self {
+        Config {
+            tick_rate: 1000,
+            memory_size: XO_CHIP_MEMORY_SIZE,
+            variant: Variant::XoChip,
+            quirks: Quirks {
+                shift_vy: true,
+                memory_increment: MemoryIncrement::XPlusOne,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for Config {
@@ -11,6 +241,15 @@ impl Default for Config {
         Config {
             rom_base_addr: 0x200,
             tick_rate: 10,
+            timing: Timing::default(),
+            memory_size: CHIP8_MEMORY_SIZE,
+            variant: Variant::default(),
+            quirks: Quirks::default(),
+            font: Font::default(),
+            rewind_depth: 0,
+            seed: None,
+            memory_access: MemoryAccess::default(),
+            unknown_opcode: UnknownOpcode::default(),
         }
     }
 }
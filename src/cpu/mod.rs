@@ -1,15 +1,38 @@
-mod opcodes;
+pub(crate) mod opcodes;
+mod trace;
 
-use crate::errors::ChipError;
-use crate::Screen;
+use std::sync::Arc;
+
+use crate::bus::Bus;
+use crate::errors::{ChipError, ErrorContext};
+use crate::{Config, Instruction, Random, Screen};
 use opcodes::execute;
 use opcodes::Opcode;
+pub use trace::{Registers, Trace};
 
 const NUM_REGISTERS: usize = 0x10;
 const STACK_SIZE: usize = 16;
+const NUM_RPL_FLAGS: usize = 0x10;
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
+
+/// A function called with the [Trace] of every instruction executed by [`Cpu::step`].
+pub type TraceHook = Arc<dyn Fn(&Trace) + Send + Sync>;
+
+/// A function executing the opcodes unknown to the CPU with
+/// [`UnknownOpcode::Trap`](crate::UnknownOpcode). The program counter already points to
+/// the next instruction when it is called.
+pub type UnknownOpcodeHandler =
+    Arc<dyn Fn(&mut Cpu, &mut dyn Bus, &mut Screen, u16) -> Result<(), ChipError> + Send + Sync>;
+
+/// A function executing the opcodes registered with [`Cpu::register_opcode`].
+/// The program counter already points to the next instruction when it is called.
+pub type OpcodeHandler =
+    Arc<dyn Fn(&mut Cpu, &mut dyn Bus, &mut Screen, u16) -> Result<(), ChipError> + Send + Sync>;
 
 /// The CPU of the machine. In charge of interpreting all the commands from
 /// the loaded ROM.
+#[derive(Clone)]
 pub struct Cpu {
     // Registers
     pub v: [u8; NUM_REGISTERS],
@@ -20,6 +43,36 @@ pub struct Cpu {
     pub timer_sound: u8,
     pub stack: [u16; STACK_SIZE],
     pub keypad: [bool; 16],
+    /// The keys pressed with [`Chip8::key_down`](crate::Chip8::key_down) since the end of
+    /// the last frame, one bit per key. FX0A consumes them, so a press is seen even when
+    /// the key is released before the instruction runs.
+    pub key_presses: u16,
+    /// The keys released with [`Chip8::key_up`](crate::Chip8::key_up) since the end of the
+    /// last frame, one bit per key.
+    pub key_releases: u16,
+    /// The Super-Chip RPL user flags written by FX75 and read by FX85.
+    pub rpl: [u8; NUM_RPL_FLAGS],
+    /// Set when the Super-Chip 00FD exit instruction is executed. A halted CPU
+    /// ignores any further steps until reset.
+    pub halted: bool,
+    /// Set after drawing a sprite when the display wait quirk is enabled. The CPU
+    /// ignores any further steps until the flag is cleared at the start of the next frame.
+    pub waiting_vblank: bool,
+    /// The key FX0A saw pressed and is waiting to be released.
+    pub pressed_key: Option<u8>,
+    /// The XO-CHIP 1-bit audio sample buffer loaded by F002.
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    /// Set once F002 loaded an audio pattern, which replaces the default tone even when
+    /// all of its bits are zero.
+    pub pattern_loaded: bool,
+    /// The XO-CHIP playback rate of the audio pattern, set by FX3A.
+    pub pitch: u8,
+    /// The random number generator used by CXNN.
+    pub random: Random,
+    trace_hook: Option<TraceHook>,
+    unknown_opcode_handler: Option<UnknownOpcodeHandler>,
+    // The (mask, pattern) of the registered opcodes with their handlers
+    opcode_handlers: Vec<(u16, u16, OpcodeHandler)>,
 }
 
 impl Cpu {
@@ -49,33 +102,143 @@ impl Cpu {
         Ok(value)
     }
 
-    /// Performs a Fetch-Decode-Execute cycle.
-    pub fn step(&mut self, memory: &mut [u8], screen: &mut Screen) -> Result<(), ChipError> {
+    /// Performs a Fetch-Decode-Execute cycle, accessing memory through the [Bus].
+    ///
+    /// Any error is wrapped in a [`ChipError::Fault`] holding the failing instruction and
+    /// the state of the machine. Use [`ChipError::root`] to get the underlying error.
+    pub fn step<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        screen: &mut Screen,
+        config: &Config,
+    ) -> Result<(), ChipError> {
+        if self.halted || self.waiting_vblank {
+            return Ok(());
+        }
+
+        // Only pay for the snapshot when someone is listening
+        let pc = self.pc;
+        let traced = self
+            .trace_hook
+            .is_some()
+            .then(|| (self.registers(), Instruction::decode(bus, pc)));
+
         // Fetch
-        let opcode_hex = self.fetch(memory)?;
+        let opcode_hex = self.fetch(bus).map_err(|e| self.fault(pc, None, e))?;
 
         // Decode
         let opcode = Opcode::from(opcode_hex);
 
         // Execute
-        execute(opcode, self, memory, screen)?;
+        execute(opcode, self, bus, screen, config)
+            .map_err(|e| self.fault(pc, Some(opcode_hex), e))?;
+
+        if let (Some(hook), Some((before, instruction))) = (&self.trace_hook, traced) {
+            hook(&Trace {
+                pc,
+                opcode: opcode_hex,
+                instruction: instruction.unwrap_or(Instruction::from(opcode_hex)),
+                before,
+                after: self.registers(),
+            });
+        }
 
         Ok(())
     }
 
-    fn fetch(&mut self, memory: &mut [u8]) -> Result<u16, ChipError> {
-        if (self.pc + 1) >= memory.len() {
-            return Err(ChipError::AddressOutOfBounds {
-                address: self.pc + 1,
-                limit: memory.len(),
-            });
+    fn fault(&self, pc: usize, opcode: Option<u16>, error: ChipError) -> ChipError {
+        // The stack holds the return addresses, the calls are right before them
+        let backtrace = self.stack[1..=self.sp]
+            .iter()
+            .rev()
+            .map(|&address| (address as usize).saturating_sub(2))
+            .collect();
+
+        ChipError::Fault {
+            context: ErrorContext {
+                pc,
+                opcode,
+                i: self.i,
+                stack_depth: self.sp,
+                backtrace,
+            },
+            error: Box::new(error),
         }
+    }
+
+    /// Call the hook after every instruction executed by [`Cpu::step`], replacing any
+    /// previous hook. The hook is kept across resets and loaded save states.
+    pub fn set_trace_hook(&mut self, hook: impl Fn(&Trace) + Send + Sync + 'static) {
+        self.trace_hook = Some(Arc::new(hook));
+    }
+
+    /// Stop tracing the executed instructions.
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+    }
 
-        let hi = memory[self.pc] as u16;
-        let lo = memory[self.pc + 1] as u16;
+    /// Execute the unknown opcodes with the handler when [`Config::unknown_opcode`] is
+    /// [`UnknownOpcode::Trap`](crate::UnknownOpcode), replacing any previous handler.
+    /// The handler is kept across resets and loaded save states.
+    pub fn set_unknown_opcode_handler(
+        &mut self,
+        handler: impl Fn(&mut Cpu, &mut dyn Bus, &mut Screen, u16) -> Result<(), ChipError>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.unknown_opcode_handler = Some(Arc::new(handler));
+    }
 
+    /// Remove the handler of the unknown opcodes.
+    pub fn clear_unknown_opcode_handler(&mut self) {
+        self.unknown_opcode_handler = None;
+    }
+
+    /// Execute the opcodes matching the pattern with the handler instead of the built-in
+    /// instructions. An opcode matches when `opcode & mask == pattern`, so `(0xF00F, 0x5001)`
+    /// claims every 5XY1 opcode. The handlers are checked in the order they were registered
+    /// and are kept across resets and loaded save states.
+    pub fn register_opcode(
+        &mut self,
+        mask: u16,
+        pattern: u16,
+        handler: impl Fn(&mut Cpu, &mut dyn Bus, &mut Screen, u16) -> Result<(), ChipError>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.opcode_handlers
+            .push((mask, pattern & mask, Arc::new(handler)));
+    }
+
+    /// Remove the handlers of all the registered opcodes.
+    pub fn clear_registered_opcodes(&mut self) {
+        self.opcode_handlers.clear();
+    }
+
+    pub(crate) fn opcode_handler(&self, opcode: u16) -> Option<OpcodeHandler> {
+        self.opcode_handlers
+            .iter()
+            .find(|(mask, pattern, _)| opcode & mask == *pattern)
+            .map(|(_, _, handler)| handler.clone())
+    }
+
+    /// Take a snapshot of the registers.
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            timer_delay: self.timer_delay,
+            timer_sound: self.timer_sound,
+        }
+    }
+
+    fn fetch(&mut self, bus: &impl Bus) -> Result<u16, ChipError> {
         // The CHIP-8 is big endian
-        let opcode: u16 = (hi << 8) | lo;
+        let opcode = bus.fetch(self.pc)?;
         self.pc += 2;
 
         Ok(opcode)
@@ -91,6 +254,15 @@ impl Cpu {
         self.timer_sound = 0;
         self.stack = [0; STACK_SIZE];
         self.keypad = [false; 16];
+        self.key_presses = 0;
+        self.key_releases = 0;
+        self.rpl = [0; NUM_RPL_FLAGS];
+        self.halted = false;
+        self.waiting_vblank = false;
+        self.pressed_key = None;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pattern_loaded = false;
+        self.pitch = DEFAULT_PITCH;
     }
 }
 
@@ -105,6 +277,19 @@ impl Default for Cpu {
             timer_sound: 0,
             stack: [0; STACK_SIZE],
             keypad: [false; 16],
+            key_presses: 0,
+            key_releases: 0,
+            rpl: [0; NUM_RPL_FLAGS],
+            halted: false,
+            waiting_vblank: false,
+            pressed_key: None,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            random: Random::default(),
+            trace_hook: None,
+            unknown_opcode_handler: None,
+            opcode_handlers: Vec::new(),
         }
     }
 }
@@ -155,4 +340,75 @@ mod tests {
         let e = cpu.pop();
         assert!(matches!(e, Err(ChipError::StackUnderflow())));
     }
+
+    #[test]
+    fn fault_context() {
+        let mut cpu = Cpu::default();
+        let mut screen = Screen::default();
+        let config = Config::default();
+        let mut memory = [0; 0x20];
+        memory[0x00..0x02].copy_from_slice(&[0x20, 0x10]);
+        memory[0x10..0x12].copy_from_slice(&[0xA1, 0x23]);
+        memory[0x12..0x14].copy_from_slice(&[0x20, 0x18]);
+        memory[0x18..0x1A].copy_from_slice(&[0xF0, 0xFF]);
+
+        for _ in 0..3 {
+            cpu.step(&mut memory, &mut screen, &config).unwrap();
+        }
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(
+            e.root(),
+            ChipError::OpcodeNotImplemented { opcode: 0xF0FF }
+        ));
+        let context = e.context().unwrap();
+        assert_eq!(context.pc, 0x18);
+        assert_eq!(context.opcode, Some(0xF0FF));
+        assert_eq!(context.i, 0x123);
+        assert_eq!(context.stack_depth, 2);
+        assert_eq!(context.backtrace, [0x12, 0x00]);
+        assert_eq!(
+            e.to_string(),
+            "The opcode 0xf0ff is not implemented \
+             (PC=0018 opcode=F0FF I=0123 stack depth=2, called from 0012 <- 0000)"
+        );
+        // The message already holds the error, so error chains don't repeat it
+        assert!(std::error::Error::source(&e).is_none());
+
+        // Errors while fetching have no opcode
+        cpu.pc = 0x1F;
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(e.root(), ChipError::AddressOutOfBounds { .. }));
+        assert_eq!(e.context().unwrap().opcode, None);
+    }
+
+    #[test]
+    fn trace_hook() {
+        let mut memory = [0; 0x300];
+        memory[0x200..0x208].copy_from_slice(&[0x6A, 0x2B, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0]);
+        let mut screen = Screen::default();
+        let config = Config::xo_chip();
+        let mut cpu = Cpu {
+            pc: 0x200,
+            ..Default::default()
+        };
+
+        let traces = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = traces.clone();
+        cpu.set_trace_hook(move |trace| log.lock().unwrap().push(*trace));
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        cpu.clear_trace_hook();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+
+        let traces = traces.lock().unwrap();
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0].pc, 0x200);
+        assert_eq!(traces[0].opcode, 0x6A2B);
+        assert_eq!(traces[0].before.v[0xA], 0x00);
+        assert_eq!(traces[0].after.v[0xA], 0x2B);
+        assert_eq!(traces[0].after.pc, 0x202);
+        assert_eq!(traces[1].instruction, Instruction::LoadLong(Some(0x1234)));
+        assert_eq!(traces[1].after.i, 0x1234);
+        assert!(traces[0].to_string().starts_with("0200: 6A2B LD VA, #2B"));
+    }
 }
@@ -1,15 +1,16 @@
 use rand::Rng;
 
 use super::Cpu;
+use crate::bus::Bus;
 use crate::errors::ChipError;
-use crate::memory::FONT_BASE_ADDRESS;
-use crate::Screen;
+use crate::memory::{BIG_FONT_BASE_ADDRESS, FONT_BASE_ADDRESS};
+use crate::{Config, Instruction, MemoryAccess, MemoryIncrement, Screen, UnknownOpcode};
 
 pub struct Opcode {
-    hex: u16,
+    pub hex: u16,
 
     // First nibble
-    prefix: u8,
+    pub prefix: u8,
     // Second nibble
     pub x: u8,
     // Third nibble
@@ -19,7 +20,7 @@ pub struct Opcode {
     // Second + Third nibble
     pub nn: u8,
     // Second + Third + Fourth nibble
-    nnn: u16,
+    pub nnn: u16,
 }
 
 impl From<u16> for Opcode {
@@ -36,138 +37,268 @@ impl From<u16> for Opcode {
     }
 }
 
-pub fn execute(
+pub fn execute<B: Bus>(
     opcode: Opcode,
     cpu: &mut Cpu,
-    memory: &mut [u8],
+    bus: &mut B,
     screen: &mut Screen,
+    config: &Config,
 ) -> Result<(), ChipError> {
-    let mut rng = rand::thread_rng();
-
-    match opcode.prefix {
-        0x0 => execute_prefix_0(opcode, cpu, screen)?,
-        0x1 => cpu.pc = opcode.nnn as usize,
-        0x2 => call_subroutine(opcode, cpu)?,
-        0x3 => skip_if(cpu.v[opcode.x as usize] == opcode.nn, cpu),
-        0x4 => skip_if(cpu.v[opcode.x as usize] != opcode.nn, cpu),
-        0x5 => skip_if(cpu.v[opcode.x as usize] == cpu.v[opcode.y as usize], cpu),
-        0x6 => cpu.v[opcode.x as usize] = opcode.nn,
-        0x7 => cpu.v[opcode.x as usize] = cpu.v[opcode.x as usize].wrapping_add(opcode.nn),
-        0x8 => execute_prefix_8(opcode, cpu)?,
-        0x9 => skip_if(cpu.v[opcode.x as usize] != cpu.v[opcode.y as usize], cpu),
-        0xA => cpu.i = opcode.nnn,
-        0xB => cpu.pc = opcode.nnn as usize + cpu.v[0] as usize,
-        0xC => cpu.v[opcode.x as usize] = rng.gen_range(0x00..0xFF) & opcode.nn,
-        0xD => draw_sprite(opcode, cpu, memory, screen)?,
-        0xE => execute_prefix_e(opcode, cpu)?,
-        0xF => execute_prefix_f(opcode, cpu, memory)?,
-        _ => return Err(ChipError::OpcodeNotImplemented { opcode: opcode.hex }),
+    // The registered opcodes take precedence over the built-in instructions
+    if let Some(handler) = cpu.opcode_handler(opcode.hex) {
+        return handler(cpu, bus, screen, opcode.hex);
     }
 
-    Ok(())
+    match execute_opcode(opcode, cpu, bus, screen, config) {
+        Err(ChipError::OpcodeNotImplemented { opcode }) => {
+            unknown_opcode(opcode, cpu, bus, screen, config)
+        }
+        result => result,
+    }
 }
 
-fn execute_prefix_0(opcode: Opcode, cpu: &mut Cpu, screen: &mut Screen) -> Result<(), ChipError> {
-    match opcode.hex {
-        0x00E0 => screen.clear_screen(),
-        0x00EE => cpu.pc = cpu.pop()? as usize,
-        _ => (),
+fn unknown_opcode(
+    opcode: u16,
+    cpu: &mut Cpu,
+    bus: &mut dyn Bus,
+    screen: &mut Screen,
+    config: &Config,
+) -> Result<(), ChipError> {
+    match config.unknown_opcode {
+        UnknownOpcode::Skip => (),
+        UnknownOpcode::Halt => cpu.halted = true,
+        UnknownOpcode::Trap if cpu.unknown_opcode_handler.is_some() => {
+            // The handler needs the CPU, so it can't stay borrowed
+            let handler = cpu.unknown_opcode_handler.clone().unwrap();
+            handler(cpu, bus, screen, opcode)?;
+        }
+        UnknownOpcode::SkipMachineCode if opcode >> 12 == 0x0 => (),
+        UnknownOpcode::SkipMachineCode | UnknownOpcode::Error | UnknownOpcode::Trap => {
+            return Err(ChipError::OpcodeNotImplemented { opcode });
+        }
     }
 
     Ok(())
 }
 
-fn execute_prefix_8(opcode: Opcode, cpu: &mut Cpu) -> Result<(), ChipError> {
-    match opcode.n {
-        0x0 => cpu.v[opcode.x as usize] = cpu.v[opcode.y as usize],
-        0x1 => cpu.v[opcode.x as usize] |= cpu.v[opcode.y as usize],
-        0x2 => cpu.v[opcode.x as usize] &= cpu.v[opcode.y as usize],
-        0x3 => cpu.v[opcode.x as usize] ^= cpu.v[opcode.y as usize],
-        0x4 => add_registers(opcode.x, opcode.y, cpu),
-        0x5 => sub_registers(opcode.x, opcode.x, opcode.y, cpu),
-        0x6 => shift_right(opcode.x, cpu),
-        0x7 => sub_registers(opcode.x, opcode.y, opcode.x, cpu),
-        0xE => shift_left(opcode.x, cpu),
-        _ => {
-            return Err(ChipError::OpcodeNotImplemented { opcode: opcode.hex });
+fn execute_opcode(
+    opcode: Opcode,
+    cpu: &mut Cpu,
+    bus: &mut impl Bus,
+    screen: &mut Screen,
+    config: &Config,
+) -> Result<(), ChipError> {
+    let schip = config.variant.has_schip();
+    let xo_chip = config.variant.has_xo_chip();
+
+    // The disassembler decodes the opcodes with the same table
+    match Instruction::from(opcode.hex) {
+        Instruction::Clear => screen.clear_screen(),
+        Instruction::Return => cpu.pc = cpu.pop()? as usize,
+        Instruction::ScrollDown(n) if schip => screen.scroll_down(n as usize),
+        Instruction::ScrollUp(n) if xo_chip => screen.scroll_up(n as usize),
+        Instruction::ScrollRight if schip => screen.scroll_right(4),
+        Instruction::ScrollLeft if schip => screen.scroll_left(4),
+        Instruction::Exit if schip => cpu.halted = true,
+        Instruction::Lores if schip => screen.set_hires(false),
+        Instruction::Hires if schip => screen.set_hires(true),
+        Instruction::Jump(nnn) => cpu.pc = nnn as usize,
+        Instruction::Call(nnn) => call_subroutine(nnn, cpu)?,
+        Instruction::SkipEqualByte { x, nn } => skip_if(cpu.v[x as usize] == nn, cpu, bus, config),
+        Instruction::SkipNotEqualByte { x, nn } => {
+            skip_if(cpu.v[x as usize] != nn, cpu, bus, config)
+        }
+        Instruction::SkipEqual { x, y } => {
+            skip_if(cpu.v[x as usize] == cpu.v[y as usize], cpu, bus, config)
+        }
+        Instruction::StoreRange { .. } if xo_chip => {
+            store_register_range(opcode, cpu, bus, config)?
         }
+        Instruction::RetrieveRange { .. } if xo_chip => {
+            retrieve_register_range(opcode, cpu, bus, config)?
+        }
+        Instruction::SetByte { x, nn } => cpu.v[x as usize] = nn,
+        Instruction::AddByte { x, nn } => cpu.v[x as usize] = cpu.v[x as usize].wrapping_add(nn),
+        Instruction::Set { x, y } => cpu.v[x as usize] = cpu.v[y as usize],
+        Instruction::Or { x, y } => bitwise(x, y, |lhs, rhs| lhs | rhs, cpu, config),
+        Instruction::And { x, y } => bitwise(x, y, |lhs, rhs| lhs & rhs, cpu, config),
+        Instruction::Xor { x, y } => bitwise(x, y, |lhs, rhs| lhs ^ rhs, cpu, config),
+        Instruction::Add { x, y } => add_registers(x, y, cpu),
+        Instruction::Sub { x, y } => sub_registers(x, x, y, cpu),
+        Instruction::ShiftRight { x, y } => shift_right(x, shift_source(x, y, config), cpu),
+        Instruction::SubReverse { x, y } => sub_registers(x, y, x, cpu),
+        Instruction::ShiftLeft { x, y } => shift_left(x, shift_source(x, y, config), cpu),
+        Instruction::SkipNotEqual { x, y } => {
+            skip_if(cpu.v[x as usize] != cpu.v[y as usize], cpu, bus, config)
+        }
+        Instruction::SetI(nnn) => cpu.i = nnn,
+        Instruction::JumpOffset(_) => jump_with_offset(opcode, cpu, config),
+        Instruction::Random { x, nn } => cpu.v[x as usize] = cpu.random.gen::<u8>() & nn,
+        Instruction::Draw { .. } => draw_sprite(opcode, cpu, bus, screen, config)?,
+        Instruction::SkipKey(x) => skip_if(cpu.keypad[key(x, cpu)], cpu, bus, config),
+        Instruction::SkipNotKey(x) => skip_if(!cpu.keypad[key(x, cpu)], cpu, bus, config),
+        Instruction::LoadLong(_) if xo_chip => load_long_address(cpu, bus)?,
+        Instruction::Plane(x) if xo_chip => screen.select_planes(x),
+        Instruction::Audio if xo_chip => load_audio_pattern(cpu, bus, config)?,
+        Instruction::GetDelay(x) => cpu.v[x as usize] = cpu.timer_delay,
+        Instruction::WaitKey(_) => get_input(opcode, cpu, config),
+        Instruction::SetDelay(x) => cpu.timer_delay = cpu.v[x as usize],
+        Instruction::SetSound(x) => cpu.timer_sound = cpu.v[x as usize],
+        Instruction::AddI(x) => cpu.i = cpu.i.wrapping_add(cpu.v[x as usize] as u16),
+        // Only the low nibble selects a character, like the original interpreters
+        Instruction::Font(x) => {
+            cpu.i = (FONT_BASE_ADDRESS + (cpu.v[x as usize] & 0xF) as usize * 5) as u16
+        }
+        Instruction::BigFont(x) if schip => {
+            cpu.i = (BIG_FONT_BASE_ADDRESS + (cpu.v[x as usize] & 0xF) as usize * 10) as u16
+        }
+        Instruction::Bcd(_) => store_bcd(opcode, cpu, bus, config)?,
+        Instruction::Pitch(x) if xo_chip => cpu.pitch = cpu.v[x as usize],
+        Instruction::Store(_) => store_registers(opcode, cpu, bus, config)?,
+        Instruction::Retrieve(_) => retrieve_registers(opcode, cpu, bus, config)?,
+        Instruction::StoreFlags(_) if schip => store_rpl_flags(opcode, cpu),
+        Instruction::RetrieveFlags(_) if schip => retrieve_rpl_flags(opcode, cpu),
+        _ => return Err(ChipError::OpcodeNotImplemented { opcode: opcode.hex }),
     }
 
     Ok(())
 }
 
-fn execute_prefix_e(opcode: Opcode, cpu: &mut Cpu) -> Result<(), ChipError> {
-    match opcode.hex & 0x00FF {
-        0x9E => skip_if(cpu.keypad[cpu.v[opcode.x as usize] as usize], cpu),
-        0xA1 => skip_if(!cpu.keypad[cpu.v[opcode.x as usize] as usize], cpu),
-        _ => {
-            return Err(ChipError::OpcodeNotImplemented { opcode: opcode.hex });
-        }
+// 8XY1, 8XY2 and 8XY3
+fn bitwise(x: u8, y: u8, op: impl Fn(u8, u8) -> u8, cpu: &mut Cpu, config: &Config) {
+    cpu.v[x as usize] = op(cpu.v[x as usize], cpu.v[y as usize]);
+
+    if config.quirks.vf_reset {
+        cpu.v[0xF] = 0;
     }
-    Ok(())
 }
 
-fn execute_prefix_f(opcode: Opcode, cpu: &mut Cpu, memory: &mut [u8]) -> Result<(), ChipError> {
-    match opcode.hex & 0x00FF {
-        0x07 => cpu.v[opcode.x as usize] = cpu.timer_delay,
-        0x0A => get_input(opcode, cpu),
-        0x15 => cpu.timer_delay = cpu.v[opcode.x as usize],
-        0x18 => cpu.timer_sound = cpu.v[opcode.x as usize],
-        0x1E => cpu.i += cpu.v[opcode.x as usize] as u16,
-        0x29 => cpu.i = (FONT_BASE_ADDRESS + (cpu.v[opcode.x as usize] as usize * 5)) as u16,
-        0x33 => store_bcd(opcode, cpu, memory),
-        0x55 => store_registers(opcode, cpu, memory),
-        0x65 => retrieve_registers(opcode, cpu, memory),
-        _ => {
-            return Err(ChipError::OpcodeNotImplemented { opcode: opcode.hex });
-        }
+fn shift_source(x: u8, y: u8, config: &Config) -> u8 {
+    match config.quirks.shift_vy {
+        true => y,
+        false => x,
     }
+}
 
-    Ok(())
+// Only the low nibble selects a key, like the original interpreters
+fn key(x: u8, cpu: &Cpu) -> usize {
+    (cpu.v[x as usize] & 0xF) as usize
 }
 
-fn call_subroutine(opcode: Opcode, cpu: &mut Cpu) -> Result<(), ChipError> {
+fn call_subroutine(nnn: u16, cpu: &mut Cpu) -> Result<(), ChipError> {
     cpu.push(cpu.pc as u16)?;
-    cpu.pc = opcode.nnn as usize;
+    cpu.pc = nnn as usize;
 
     Ok(())
 }
 
-fn skip_if(skip: bool, cpu: &mut Cpu) {
+fn jump_with_offset(opcode: Opcode, cpu: &mut Cpu, config: &Config) {
+    let offset_reg = match config.quirks.jump_with_vx {
+        true => opcode.x,
+        false => 0,
+    };
+
+    cpu.pc = opcode.nnn as usize + cpu.v[offset_reg as usize] as usize;
+}
+
+fn skip_if(skip: bool, cpu: &mut Cpu, bus: &impl Bus, config: &Config) {
     if skip {
+        // The XO-CHIP F000 NNNN instruction is 4 bytes long and must be skipped as a whole
+        if config.variant.has_xo_chip() && bus.fetch(cpu.pc).ok() == Some(0xF000) {
+            cpu.pc += 2;
+        }
+
         cpu.pc += 2;
     }
 }
 
+fn load_long_address(cpu: &mut Cpu, bus: &impl Bus) -> Result<(), ChipError> {
+    cpu.i = cpu.fetch(bus)?;
+
+    Ok(())
+}
+
 fn draw_sprite(
     opcode: Opcode,
     cpu: &mut Cpu,
-    memory: &mut [u8],
+    bus: &mut impl Bus,
     screen: &mut Screen,
+    config: &Config,
 ) -> Result<(), ChipError> {
-    let sprite_height = opcode.n as usize;
-    let sprite_x = cpu.v[opcode.x as usize] as usize;
-    let sprite_y = cpu.v[opcode.y as usize] as usize;
-    let sprite_base_addr = cpu.i as usize;
-    let mut collided = false;
-
-    for y in 0..sprite_height {
-        let sprite_hslice: u8 = memory[sprite_base_addr + y];
-
-        for x in 0..8 {
-            if (sprite_hslice & (0x80 >> x)) != 0 {
-                let pos_x = (sprite_x + x) % screen.width;
-                let pos_y = (sprite_y + y) % screen.height;
-                collided |= screen.get_pixel(pos_x, pos_y);
-                screen.toggle_pixel(pos_x, pos_y);
+    // DXY0 draws a 16x16 Super-Chip sprite made of two bytes per row
+    let (sprite_width, sprite_height) = match opcode.n {
+        0 if config.variant.has_schip() => (16, 16),
+        n => (8, n as usize),
+    };
+    // The starting position always wraps, only the sprite pixels may get clipped
+    let sprite_x = cpu.v[opcode.x as usize] as usize % screen.width;
+    let sprite_y = cpu.v[opcode.y as usize] as usize % screen.height;
+    let clip = config.quirks.clip_sprites;
+    let sprite_size = sprite_height * sprite_width / 8;
+    let planes = screen.selected_planes().count_ones() as usize;
+    let mut collided_rows = [false; 16];
+
+    // With both XO-CHIP planes selected, the sprite data for the second plane
+    // immediately follows the data for the first one
+    let mut sprite = [0; 64];
+    for (byte, addr) in sprite.iter_mut().zip(addresses(
+        cpu.i as usize,
+        sprite_size * planes,
+        bus,
+        config,
+    )?) {
+        *byte = bus.read(addr)?;
+    }
+    let mut sprite_offset = 0;
+
+    for plane in [0b01, 0b10] {
+        if screen.selected_planes() & plane == 0 {
+            continue;
+        }
+
+        for y in 0..sprite_height {
+            let sprite_hslice: u16 = match sprite_width {
+                16 => {
+                    let row = sprite_offset + y * 2;
+                    ((sprite[row] as u16) << 8) | sprite[row + 1] as u16
+                }
+                _ => (sprite[sprite_offset + y] as u16) << 8,
+            };
+
+            if clip && sprite_y + y >= screen.height {
+                break;
+            }
+
+            for x in 0..sprite_width {
+                if clip && sprite_x + x >= screen.width {
+                    break;
+                }
+
+                if (sprite_hslice & (0x8000 >> x)) != 0 {
+                    let pos_x = (sprite_x + x) % screen.width;
+                    let pos_y = (sprite_y + y) % screen.height;
+                    collided_rows[y] |= screen.get_plane_pixel(pos_x, pos_y, plane);
+                    screen.toggle_plane_pixel(pos_x, pos_y, plane);
+                }
             }
         }
+
+        sprite_offset += sprite_size;
     }
 
-    if collided {
-        cpu.v[0xF] = 1;
+    let collisions = collided_rows.iter().filter(|&&row| row).count();
+    cpu.v[0xF] = if config.quirks.collision_rows && screen.is_hires() {
+        let clipped_rows = match clip {
+            true => (sprite_y + sprite_height).saturating_sub(screen.height),
+            false => 0,
+        };
+        (collisions + clipped_rows) as u8
     } else {
-        cpu.v[0xF] = 0;
+        (collisions > 0) as u8
+    };
+
+    if config.quirks.display_wait {
+        cpu.waiting_vblank = true;
     }
 
     Ok(())
@@ -191,30 +322,59 @@ fn sub_registers(reg_store: u8, reg_lhs: u8, reg_rhs: u8, cpu: &mut Cpu) {
     };
 }
 
-fn shift_right(reg: u8, cpu: &mut Cpu) {
-    cpu.v[0xF] = cpu.v[reg as usize] & 0x01;
-    cpu.v[reg as usize] >>= 1;
+fn shift_right(reg_store: u8, reg_src: u8, cpu: &mut Cpu) {
+    let value = cpu.v[reg_src as usize];
+    cpu.v[reg_store as usize] = value >> 1;
+    cpu.v[0xF] = value & 0x01;
 }
 
-fn shift_left(reg: u8, cpu: &mut Cpu) {
-    cpu.v[0xF] = (cpu.v[reg as usize] >> 7) & 0x01;
-    cpu.v[reg as usize] <<= 1;
+fn shift_left(reg_store: u8, reg_src: u8, cpu: &mut Cpu) {
+    let value = cpu.v[reg_src as usize];
+    cpu.v[reg_store as usize] = value << 1;
+    cpu.v[0xF] = (value >> 7) & 0x01;
 }
 
-fn get_input(opcode: Opcode, cpu: &mut Cpu) {
-    let input = cpu.keypad.iter().position(|&x| x);
+fn get_input(opcode: Opcode, cpu: &mut Cpu, config: &Config) {
+    // A latched press counts even if the key was released before this instruction
+    let held = (0..16).fold(0, |held, key| held | (cpu.keypad[key] as u16) << key);
+    let pressed = cpu.key_presses | held;
+    if cpu.pressed_key.is_none() && pressed != 0 {
+        let key = pressed.trailing_zeros() as u8;
+        cpu.key_presses &= !(1 << key);
+        if config.quirks.key_press_wait {
+            cpu.v[opcode.x as usize] = key;
+            return;
+        }
+        cpu.pressed_key = Some(key);
+    }
 
-    match input {
-        Some(key) => cpu.v[opcode.x as usize] = key as u8,
-        None => cpu.pc -= 2,
-    };
+    // Wait for the pressed key to be released
+    if let Some(key) = cpu.pressed_key {
+        let released = cpu.key_releases & (1 << key) != 0;
+        if released || !cpu.keypad[key as usize] {
+            cpu.key_releases &= !(1 << key);
+            cpu.v[opcode.x as usize] = key;
+            cpu.pressed_key = None;
+            return;
+        }
+    }
+
+    cpu.pc -= 2;
 }
 
-fn store_bcd(opcode: Opcode, cpu: &mut Cpu, memory: &mut [u8]) {
+fn store_bcd(
+    opcode: Opcode,
+    cpu: &mut Cpu,
+    bus: &mut impl Bus,
+    config: &Config,
+) -> Result<(), ChipError> {
     let (bcd2, bcd1, bcd0) = bcd(cpu.v[opcode.x as usize]);
-    memory[cpu.i as usize] = bcd2;
-    memory[cpu.i as usize + 1] = bcd1;
-    memory[cpu.i as usize + 2] = bcd0;
+    let digits = [bcd2, bcd1, bcd0];
+    for (addr, digit) in addresses(cpu.i as usize, digits.len(), bus, config)?.zip(digits) {
+        bus.write(addr, digit)?;
+    }
+
+    Ok(())
 }
 
 fn bcd(input: u8) -> (u8, u8, u8) {
@@ -252,15 +412,126 @@ fn bcd(input: u8) -> (u8, u8, u8) {
     (bcd2, bcd1, bcd0)
 }
 
-fn store_registers(opcode: Opcode, cpu: &mut Cpu, memory: &mut [u8]) {
+fn store_registers(
+    opcode: Opcode,
+    cpu: &mut Cpu,
+    bus: &mut impl Bus,
+    config: &Config,
+) -> Result<(), ChipError> {
+    let count = (opcode.x + 1) as usize;
+    for (reg, addr) in addresses(cpu.i as usize, count, bus, config)?.enumerate() {
+        bus.write(addr, cpu.v[reg])?;
+    }
+
+    increment_i(opcode, cpu, config);
+
+    Ok(())
+}
+
+fn retrieve_registers(
+    opcode: Opcode,
+    cpu: &mut Cpu,
+    bus: &mut impl Bus,
+    config: &Config,
+) -> Result<(), ChipError> {
+    let count = (opcode.x + 1) as usize;
+    for (reg, addr) in addresses(cpu.i as usize, count, bus, config)?.enumerate() {
+        cpu.v[reg] = bus.read(addr)?;
+    }
+
+    increment_i(opcode, cpu, config);
+
+    Ok(())
+}
+
+fn increment_i(opcode: Opcode, cpu: &mut Cpu, config: &Config) {
+    cpu.i = cpu.i.wrapping_add(match config.quirks.memory_increment {
+        MemoryIncrement::None => 0,
+        MemoryIncrement::X => opcode.x as u16,
+        MemoryIncrement::XPlusOne => opcode.x as u16 + 1,
+    });
+}
+
+fn store_register_range(
+    opcode: Opcode,
+    cpu: &mut Cpu,
+    bus: &mut impl Bus,
+    config: &Config,
+) -> Result<(), ChipError> {
+    let regs = register_range(opcode);
+    for (addr, reg) in addresses(cpu.i as usize, regs.len(), bus, config)?.zip(regs) {
+        bus.write(addr, cpu.v[reg])?;
+    }
+
+    Ok(())
+}
+
+fn retrieve_register_range(
+    opcode: Opcode,
+    cpu: &mut Cpu,
+    bus: &mut impl Bus,
+    config: &Config,
+) -> Result<(), ChipError> {
+    let regs = register_range(opcode);
+    for (addr, reg) in addresses(cpu.i as usize, regs.len(), bus, config)?.zip(regs) {
+        cpu.v[reg] = bus.read(addr)?;
+    }
+
+    Ok(())
+}
+
+// The registers VX to VY inclusive, in descending order when X > Y
+fn register_range(opcode: Opcode) -> impl ExactSizeIterator<Item = usize> {
+    let (x, y) = (opcode.x as usize, opcode.y as usize);
+    let len = x.abs_diff(y) + 1;
+
+    (0..len).map(move |k| if x <= y { x + k } else { x - k })
+}
+
+fn load_audio_pattern(cpu: &mut Cpu, bus: &mut impl Bus, config: &Config) -> Result<(), ChipError> {
+    let pattern_size = cpu.audio_pattern.len();
+    for (byte, addr) in
+        cpu.audio_pattern
+            .iter_mut()
+            .zip(addresses(cpu.i as usize, pattern_size, bus, config)?)
+    {
+        *byte = bus.read(addr)?;
+    }
+    cpu.pattern_loaded = true;
+
+    Ok(())
+}
+
+// The addresses of `len` bytes starting at `base`. They are all checked before any of
+// them is accessed, so a failing instruction leaves the memory and registers untouched.
+fn addresses(
+    base: usize,
+    len: usize,
+    bus: &impl Bus,
+    config: &Config,
+) -> Result<impl Iterator<Item = usize>, ChipError> {
+    let limit = bus.size();
+    let end = base + len;
+    let wrap = config.memory_access == MemoryAccess::Wrap && limit > 0;
+    if end > limit && !wrap {
+        return Err(ChipError::AddressOutOfBounds {
+            address: base.max(limit),
+            limit,
+        });
+    }
+
+    Ok((base..end).map(move |addr| addr % limit))
+}
+
+fn store_rpl_flags(opcode: Opcode, cpu: &mut Cpu) {
     for i in 0..(opcode.x + 1) as usize {
-        memory[cpu.i as usize + i] = cpu.v[i];
+        cpu.rpl[i] = cpu.v[i];
     }
 }
 
-fn retrieve_registers(opcode: Opcode, cpu: &mut Cpu, memory: &mut [u8]) {
+fn retrieve_rpl_flags(opcode: Opcode, cpu: &mut Cpu) {
     for i in 0..(opcode.x + 1) as usize {
-        cpu.v[i] = memory[cpu.i as usize + i];
+        cpu.v[i] = cpu.rpl[i];
     }
 }
 
@@ -270,396 +541,761 @@ mod tests {
     use super::Cpu;
     use super::Screen;
     use crate::errors::ChipError;
+    use crate::memory::{BIG_FONT_BASE_ADDRESS, FONT_BASE_ADDRESS};
+    use crate::{
+        Config, MemoryAccess, MemoryIncrement, Random, UnknownOpcode, Variant, XO_CHIP_MEMORY_SIZE,
+    };
 
-    fn test_setup() -> (Cpu, Screen) {
-        (Cpu::default(), Screen::default())
+    fn test_setup() -> (Cpu, Screen, Config) {
+        (Cpu::default(), Screen::default(), Config::default())
+    }
+
+    fn xo_chip_setup() -> (Cpu, Screen, Config) {
+        let config = Config {
+            memory_size: XO_CHIP_MEMORY_SIZE,
+            variant: Variant::XoChip,
+            ..Default::default()
+        };
+
+        (Cpu::default(), Screen::default(), config)
     }
 
     #[test]
     fn opcode_00e0() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x00, 0xe0, 0x00, 0x00];
         screen.set_pixel(5, 10);
         screen.set_pixel(50, 30);
 
         assert!(screen.get_pixel(5, 10));
         assert!(screen.get_pixel(50, 30));
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(!screen.get_pixel(5, 10));
         assert!(!screen.get_pixel(50, 30));
     }
 
     #[test]
     fn opcode_00ee() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x00, 0xee, 0x00, 0x00];
 
         assert_eq!(cpu.pc, 0);
         cpu.push(0x01).unwrap();
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 1);
 
         cpu.pc = 0;
-        let e = cpu.step(&mut memory, &mut screen);
-        assert!(matches!(e, Err(ChipError::StackUnderflow())));
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(e.root(), ChipError::StackUnderflow()));
+    }
+
+    #[test]
+    fn opcode_00cn() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0x00, 0xc3, 0x00, 0x00];
+        screen.set_pixel(5, 10);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.get_pixel(5, 10));
+        assert!(screen.get_pixel(5, 13));
+    }
+
+    #[test]
+    fn opcode_00fb() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0x00, 0xfb, 0x00, 0x00];
+        screen.set_pixel(5, 10);
+        screen.set_pixel(62, 10);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.get_pixel(5, 10));
+        assert!(screen.get_pixel(9, 10));
+        assert!(!screen.get_pixel(62, 10));
+        assert!(!screen.get_pixel(2, 11));
+    }
+
+    #[test]
+    fn opcode_00fc() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0x00, 0xfc, 0x00, 0x00];
+        screen.set_pixel(5, 10);
+        screen.set_pixel(2, 10);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.get_pixel(5, 10));
+        assert!(screen.get_pixel(1, 10));
+        assert!(!screen.get_pixel(62, 9));
+    }
+
+    #[test]
+    fn opcode_00fd() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0x00, 0xfd, 0x12, 0x00];
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(cpu.halted);
+        assert_eq!(cpu.pc, 2);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 2);
+    }
+
+    #[test]
+    fn opcode_00fe_00ff() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0x00, 0xff, 0x00, 0xfe];
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(screen.is_hires());
+        assert_eq!((screen.width, screen.height), (128, 64));
+        screen.set_pixel(127, 63);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.is_hires());
+        assert_eq!((screen.width, screen.height), (64, 32));
+        assert!(!screen.get_pixel(63, 31));
+    }
+
+    #[test]
+    fn opcode_00dn() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory: [u8; 4] = [0x00, 0xd3, 0x00, 0x00];
+        screen.set_pixel(5, 10);
+        screen.set_pixel(5, 1);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.get_pixel(5, 10));
+        assert!(screen.get_pixel(5, 7));
+        assert!(!screen.get_pixel(5, 1));
+    }
+
+    #[test]
+    fn schip_disabled() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 4] = [0x00, 0xff, 0xF0, 0x30];
+        config.variant = Variant::Chip8;
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.is_hires());
+
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(e.root(), ChipError::OpcodeNotImplemented { .. }));
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 8] = [0x01, 0x23, 0x80, 0x0F, 0xE0, 0x00, 0xF0, 0xFF];
+
+        // 0NNN is skipped by default
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 2);
+        for pc in [2, 4, 6] {
+            cpu.pc = pc;
+            let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+            assert!(matches!(e.root(), ChipError::OpcodeNotImplemented { .. }));
+        }
+
+        config.unknown_opcode = UnknownOpcode::Error;
+        for pc in [0, 2, 4, 6] {
+            cpu.pc = pc;
+            let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+            assert!(matches!(e.root(), ChipError::OpcodeNotImplemented { .. }));
+        }
+
+        config.unknown_opcode = UnknownOpcode::Skip;
+        for pc in [0, 2, 4, 6] {
+            cpu.pc = pc;
+            cpu.step(&mut memory, &mut screen, &config).unwrap();
+            assert_eq!(cpu.pc, pc + 2);
+        }
+
+        config.unknown_opcode = UnknownOpcode::Halt;
+        cpu.pc = 0;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(cpu.halted);
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 2);
+    }
+
+    #[test]
+    fn unknown_opcode_trap() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 4] = [0x01, 0x23, 0xF0, 0xFF];
+        config.unknown_opcode = UnknownOpcode::Trap;
+
+        // Without a handler even 0NNN fails
+        for pc in [0, 2] {
+            cpu.pc = pc;
+            let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+            assert!(matches!(e.root(), ChipError::OpcodeNotImplemented { .. }));
+        }
+
+        cpu.set_unknown_opcode_handler(|cpu, bus, _, opcode| match opcode {
+            0x0123 => {
+                cpu.v[0] = bus.read(0x3)?;
+                Ok(())
+            }
+            _ => Err(ChipError::OpcodeNotImplemented { opcode }),
+        });
+        cpu.pc = 0;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0], 0xFF);
+        assert_eq!(cpu.pc, 2);
+
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(
+            e.root(),
+            ChipError::OpcodeNotImplemented { opcode: 0xF0FF }
+        ));
+    }
+
+    #[test]
+    fn registered_opcodes() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 8] = [0x52, 0x31, 0x00, 0x07, 0x50, 0x10, 0x00, 0x00];
+
+        // VX = VX * VY
+        cpu.register_opcode(0xF00F, 0x5001, |cpu, _, _, opcode| {
+            let (x, y) = ((opcode >> 8 & 0xF) as usize, (opcode >> 4 & 0xF) as usize);
+            cpu.v[x] = cpu.v[x].wrapping_mul(cpu.v[y]);
+            Ok(())
+        });
+        // Write the screen width to memory at NNN
+        cpu.register_opcode(0xF000, 0x0000, |_, bus, screen, opcode| {
+            bus.write((opcode & 0xFFF) as usize, screen.width as u8)
+        });
+        // Never reached, the first registered opcode matches
+        cpu.register_opcode(0xFFFF, 0x5231, |_, _, _, _| unreachable!());
+
+        cpu.v[2] = 6;
+        cpu.v[3] = 7;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[2], 42);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(memory[0x7], screen.width as u8);
+
+        // The built-in instructions still run
+        cpu.v[1] = 0;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 8);
+
+        cpu.clear_registered_opcodes();
+        cpu.pc = 0;
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(e.root(), ChipError::OpcodeNotImplemented { .. }));
     }
 
     #[test]
     fn opcode_1nnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x12, 0x34, 0x00, 0x00];
 
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 0x234);
     }
 
     #[test]
     fn opcode_2nnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x00, 0x21, 0x23, 0x00];
         cpu.pc = 1;
 
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 0x123);
         assert_eq!(cpu.stack[cpu.sp], 0x03);
     }
 
     #[test]
     fn opcode_3xnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x31, 0x23, 0x00, 0x00];
 
         cpu.v[1] = 0x23;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 4);
 
         cpu.pc = 0;
         cpu.v[1] = 0x22;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 2);
     }
 
     #[test]
     fn opcode_4xnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x41, 0x23, 0x00, 0x00];
 
         cpu.v[1] = 0x32;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 4);
 
         cpu.pc = 0;
         cpu.v[1] = 0x23;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 2);
     }
 
     #[test]
     fn opcode_5xy0() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x51, 0x20, 0x00, 0x00];
 
         cpu.v[1] = 0x23;
         cpu.v[2] = 0x23;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 4);
 
         cpu.pc = 0;
         cpu.v[1] = 0x32;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 2);
     }
 
+    #[test]
+    fn opcode_5xy2() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory: [u8; 8] = [0x51, 0x32, 0x53, 0x12, 0x00, 0x00, 0x00, 0x00];
+
+        cpu.i = 4;
+        cpu.v[1] = 0x12;
+        cpu.v[2] = 0x34;
+        cpu.v[3] = 0x56;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(memory[4..8], [0x12, 0x34, 0x56, 0x00]);
+        assert_eq!(cpu.i, 4);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(memory[4..8], [0x56, 0x34, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn opcode_5xy3() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory: [u8; 8] = [0x52, 0x43, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04];
+
+        cpu.i = 4;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[1..6], [0x00, 0x01, 0x02, 0x03, 0x00]);
+
+        let (mut cpu, mut screen, config) = test_setup();
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(e.root(), ChipError::OpcodeNotImplemented { .. }));
+    }
+
     #[test]
     fn opcode_6xnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x62, 0xF1, 0x00, 0x00];
 
         assert_eq!(cpu.v[0x2], 0);
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x2], 0xF1);
     }
 
     #[test]
     fn opcode_7xnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x75, 0xA1, 0x00, 0x00];
 
         cpu.v[0x5] = 0x32;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x5], 0xD3);
     }
 
     #[test]
     fn opcode_8xy0() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x20, 0x00, 0x00];
 
         cpu.v[0x2] = 0x02;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0x02);
     }
 
     #[test]
     fn opcode_8xy1() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x21, 0x00, 0x00];
 
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0x00);
 
         cpu.pc = 0;
         cpu.v[0x1] = 0x40;
         cpu.v[0x2] = 0x12;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0x52);
     }
 
     #[test]
     fn opcode_8xy2() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x22, 0x00, 0x00];
 
         cpu.v[0x2] = 0x34;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0x00);
 
         cpu.pc = 0;
         cpu.v[0x1] = 0x12;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0x10);
     }
 
     #[test]
     fn opcode_8xy3() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x23, 0x00, 0x00];
 
         cpu.v[0x1] = 0xA7;
         cpu.v[0x2] = 0x35;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0xA7 ^ 0x35);
     }
 
     #[test]
     fn opcode_8xy4() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x24, 0x00, 0x00];
 
         cpu.v[0x1] = 0x12;
         cpu.v[0x2] = 0x34;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0x12 + 0x34);
         assert!(cpu.v[0xF] != 0x01);
 
         cpu.pc = 0;
         cpu.v[0x1] = 150;
         cpu.v[0x2] = 106;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0);
         assert!(cpu.v[0xF] == 0x01);
     }
 
     #[test]
     fn opcode_8xy5() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x25, 0x00, 0x00];
 
         cpu.v[0x1] = 100;
         cpu.v[0x2] = 60;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 40);
         assert!(cpu.v[0xF] == 0x01);
 
         cpu.pc = 0;
         cpu.v[0x1] = 30;
         cpu.v[0x2] = 31;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 255);
         assert!(cpu.v[0xF] == 0x00);
     }
 
     #[test]
     fn opcode_8xy6() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x26, 0x00, 0x00];
 
         cpu.v[0x1] = 0b1000_1010;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0b0100_0101);
         assert!(cpu.v[0xF] == 0x00);
 
         cpu.pc = 0;
         cpu.v[0x1] = 0b1001_1011;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0b0100_1101);
         assert!(cpu.v[0xF] == 0x01);
     }
 
     #[test]
     fn opcode_8xy7() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x27, 0x00, 0x00];
 
         cpu.v[0x1] = 30;
         cpu.v[0x2] = 110;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 80);
         assert!(cpu.v[0xF] == 0x01);
 
         cpu.pc = 0;
         cpu.v[0x1] = 41;
         cpu.v[0x2] = 40;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 255);
         assert!(cpu.v[0xF] == 0x00);
     }
 
     #[test]
     fn opcode_8xye() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x81, 0x2e, 0x00, 0x00];
 
         cpu.v[0x1] = 0b0100_1010;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0b1001_0100);
         assert!(cpu.v[0xF] == 0x00);
 
         cpu.pc = 0;
         cpu.v[0x1] = 0b1001_1011;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 0b0011_0110);
         assert!(cpu.v[0xF] == 0x01);
     }
 
     #[test]
     fn opcode_9xy0() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0x91, 0x20, 0x00, 0x00];
 
         cpu.v[0x1] = 0x12;
         cpu.v[0x2] = 0x12;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == 2);
 
         cpu.pc = 0;
         cpu.v[0x1] = 0x12;
         cpu.v[0x2] = 0x22;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == 4);
     }
 
     #[test]
     fn opcode_annn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xA1, 0x23, 0x00, 0x00];
 
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.i, 0x123);
     }
 
     #[test]
     fn opcode_bnnn() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xB0, 0x23, 0x00, 0x00];
 
         cpu.v[0x0] = 0x10;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == (0x10 + 0x23));
     }
 
+    #[test]
+    fn opcode_cxnn() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0xC0, 0x0F, 0xC1, 0xFF];
+
+        cpu.random = Random::new(1234);
+        let mut other = cpu.clone();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        other.step(&mut memory, &mut screen, &config).unwrap();
+        other.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0x0] & 0xF0, 0);
+        assert_eq!(cpu.v[0x0..=0x1], other.v[0x0..=0x1]);
+        assert_eq!(cpu.random, other.random);
+
+        // Every value is reachable
+        let mut memory: [u8; 4] = [0xC0, 0xFF, 0x10, 0x00];
+        cpu.pc = 0;
+        let seen = (0..4096).fold([false; 256], |mut seen, _| {
+            cpu.step(&mut memory, &mut screen, &config).unwrap();
+            cpu.step(&mut memory, &mut screen, &config).unwrap();
+            seen[cpu.v[0x0] as usize] = true;
+            seen
+        });
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn opcode_dxy0() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory = [0xFF; 34];
+        memory[0] = 0xD0;
+        memory[1] = 0x10;
+        memory[2] = 0x80;
+        memory[3] = 0x01;
+        screen.set_hires(true);
+
+        cpu.i = 2;
+        cpu.v[0x0] = 120;
+        cpu.v[0x1] = 4;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(screen.get_pixel(120, 4));
+        assert!(!screen.get_pixel(121, 4));
+        assert!(screen.get_pixel(7, 4));
+        assert!(screen.get_pixel(120, 19));
+        assert!(!screen.get_pixel(120, 20));
+        assert_eq!(cpu.v[0xF], 0);
+
+        cpu.pc = 0;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(!screen.get_pixel(120, 4));
+        assert_eq!(cpu.v[0xF], 1);
+    }
+
+    #[test]
+    fn opcode_dxyn_planes() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory: [u8; 6] = [0xF3, 0x01, 0xD0, 0x01, 0x80, 0xC0];
+
+        cpu.i = 4;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(screen.selected_planes(), 0b11);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(screen.get_pixel_color(0, 0), 0b11);
+        assert_eq!(screen.get_pixel_color(1, 0), 0b10);
+        assert_eq!(cpu.v[0xF], 0);
+
+        cpu.pc = 2;
+        screen.select_planes(0b10);
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(screen.get_pixel_color(0, 0), 0b01);
+        assert_eq!(screen.get_pixel_color(1, 0), 0b10);
+        assert_eq!(cpu.v[0xF], 1);
+    }
+
     #[test]
     fn opcode_ex9e() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xE1, 0x9E, 0x00, 0x00];
 
         cpu.v[0x1] = 0xA;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == 2);
 
         cpu.pc = 0;
         cpu.keypad[0xA] = true;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == 4);
     }
 
     #[test]
     fn opcode_exa1() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xE1, 0xA1, 0x00, 0x00];
 
         cpu.v[0x1] = 0xA;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == 4);
 
         cpu.pc = 0;
         cpu.keypad[0xA] = true;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert!(cpu.pc == 2);
     }
 
+    #[test]
+    fn opcode_f000() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory: [u8; 10] = [0xF0, 0x00, 0xAB, 0xCD, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34];
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i, 0xABCD);
+        assert_eq!(cpu.pc, 4);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 10);
+    }
+
+    #[test]
+    fn opcode_f002() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory = [0xAA; 18];
+        memory[0] = 0xF0;
+        memory[1] = 0x02;
+
+        cpu.i = 2;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.audio_pattern, [0xAA; 16]);
+        assert!(cpu.pattern_loaded);
+    }
+
     #[test]
     fn opcode_fx07() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xF1, 0x07, 0x00, 0x00];
 
         cpu.timer_delay = 5;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0x1], 5);
     }
 
     #[test]
     fn opcode_fx0a() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xF1, 0x0A, 0x00, 0x00];
 
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 0);
 
         cpu.keypad[0x2] = true;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 0);
+
+        cpu.keypad[0x2] = false;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.pc, 2);
+        assert_eq!(cpu.v[0x1], 0x2);
+    }
+
+    #[test]
+    fn quirk_key_press_wait() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        config.quirks.key_press_wait = true;
+        let mut memory: [u8; 4] = [0xF1, 0x0A, 0x00, 0x00];
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 0);
+
+        cpu.keypad[0x2] = true;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 2);
+        assert_eq!(cpu.v[0x1], 0x2);
     }
 
     #[test]
     fn opcode_fx15() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xF1, 0x15, 0x00, 0x00];
 
         cpu.v[1] = 10;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.timer_delay, 10);
     }
 
     #[test]
     fn opcode_fx18() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xF1, 0x18, 0x00, 0x00];
 
         cpu.v[1] = 15;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.timer_sound, 15);
     }
 
     #[test]
     fn opcode_fx1e() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 4] = [0xF1, 0x1E, 0x00, 0x00];
 
         cpu.i = 40;
         cpu.v[0x1] = 60;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.i, 100);
     }
 
     #[test]
     fn opcode_fx33() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 5] = [0xF0, 0x33, 0x00, 0x00, 0x00];
 
         cpu.v[0] = 123;
         cpu.i = 0x002;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(memory[0x002], 0x01);
         assert_eq!(memory[0x003], 0x02);
         assert_eq!(memory[0x004], 0x03);
@@ -667,22 +1303,32 @@ mod tests {
         cpu.v[0] = 0x97;
         cpu.i = 0x002;
         cpu.pc = 0;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(memory[0x002], 0x01);
         assert_eq!(memory[0x003], 0x05);
         assert_eq!(memory[0x004], 0x01);
     }
 
+    #[test]
+    fn opcode_fx3a() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+        let mut memory: [u8; 4] = [0xF1, 0x3A, 0x00, 0x00];
+
+        cpu.v[1] = 112;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pitch, 112);
+    }
+
     #[test]
     fn opcode_fx55() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 5] = [0xF2, 0x55, 0x00, 0x00, 0x00];
 
         cpu.i = 2;
         cpu.v[0] = 0x12;
         cpu.v[1] = 0x34;
         cpu.v[2] = 0x56;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(memory[2], 0x12);
         assert_eq!(memory[3], 0x34);
         assert_eq!(memory[4], 0x56);
@@ -690,16 +1336,204 @@ mod tests {
 
     #[test]
     fn opcode_fx65() {
-        let (mut cpu, mut screen) = test_setup();
+        let (mut cpu, mut screen, config) = test_setup();
         let mut memory: [u8; 5] = [0xF2, 0x65, 0x01, 0x02, 0x03];
 
         cpu.i = 0x002;
-        cpu.step(&mut memory, &mut screen).unwrap();
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
         assert_eq!(cpu.v[0], 0x01);
         assert_eq!(cpu.v[1], 0x02);
         assert_eq!(cpu.v[2], 0x03);
     }
 
+    #[test]
+    fn opcode_fx30() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0xF1, 0x30, 0x00, 0x00];
+
+        cpu.v[0x1] = 0x3;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i as usize, BIG_FONT_BASE_ADDRESS + 30);
+    }
+
+    #[test]
+    fn opcode_fx75() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0xF2, 0x75, 0x00, 0x00];
+
+        cpu.v[0] = 0x12;
+        cpu.v[1] = 0x34;
+        cpu.v[2] = 0x56;
+        cpu.v[3] = 0x78;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.rpl[0..4], [0x12, 0x34, 0x56, 0x00]);
+    }
+
+    #[test]
+    fn opcode_fx85() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 4] = [0xF2, 0x85, 0x00, 0x00];
+
+        cpu.rpl[0] = 0x01;
+        cpu.rpl[1] = 0x02;
+        cpu.rpl[2] = 0x03;
+        cpu.rpl[3] = 0x04;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0..4], [0x01, 0x02, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn quirk_vf_reset() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 4] = [0x81, 0x21, 0x00, 0x00];
+
+        cpu.v[0xF] = 0x5;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0xF], 0x5);
+
+        cpu.pc = 0;
+        config.quirks.vf_reset = true;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0xF], 0x0);
+    }
+
+    #[test]
+    fn quirk_shift_vy() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 4] = [0x81, 0x26, 0x81, 0x2e];
+        config.quirks.shift_vy = true;
+
+        cpu.v[0x1] = 0b1111_0000;
+        cpu.v[0x2] = 0b1000_0011;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0x1], 0b0100_0001);
+        assert_eq!(cpu.v[0xF], 0x01);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0x1], 0b0000_0110);
+        assert_eq!(cpu.v[0xF], 0x01);
+    }
+
+    #[test]
+    fn preset_xo_chip() {
+        let (mut cpu, mut screen, _) = test_setup();
+        let config = Config::xo_chip();
+        let mut memory: [u8; 6] = [0x81, 0x26, 0x81, 0x2E, 0xF1, 0x55];
+
+        // Octo shifts VY into VX like the original CHIP-8
+        cpu.v[0x2] = 0b1000_0011;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0x1], 0b0100_0001);
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0x1], 0b0000_0110);
+
+        cpu.i = 0;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i, 2);
+    }
+
+    #[test]
+    fn quirk_jump_with_vx() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 4] = [0xB1, 0x23, 0x00, 0x00];
+        config.quirks.jump_with_vx = true;
+
+        cpu.v[0x0] = 0x10;
+        cpu.v[0x1] = 0x20;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 0x123 + 0x20);
+    }
+
+    #[test]
+    fn quirk_memory_increment() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 8] = [0xF2, 0x55, 0xF2, 0x65, 0x00, 0x00, 0x00, 0x00];
+
+        config.quirks.memory_increment = MemoryIncrement::X;
+        cpu.i = 4;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i, 6);
+
+        config.quirks.memory_increment = MemoryIncrement::XPlusOne;
+        cpu.i = 4;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i, 7);
+    }
+
+    #[test]
+    fn quirk_clip_sprites() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 4] = [0xD0, 0x12, 0xFF, 0xFF];
+
+        cpu.i = 2;
+        cpu.v[0x0] = 60;
+        cpu.v[0x1] = 31;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(screen.get_pixel(63, 31));
+        assert!(screen.get_pixel(0, 31));
+        assert!(screen.get_pixel(0, 0));
+
+        screen.clear_screen();
+        cpu.pc = 0;
+        config.quirks.clip_sprites = true;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(screen.get_pixel(63, 31));
+        assert!(!screen.get_pixel(0, 31));
+        assert!(!screen.get_pixel(60, 0));
+
+        screen.clear_screen();
+        cpu.pc = 0;
+        cpu.v[0x0] = 64 + 2;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(screen.get_pixel(2, 31));
+    }
+
+    #[test]
+    fn quirk_collision_rows() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory = [0xFF; 36];
+        memory[..4].copy_from_slice(&[0xD0, 0x10, 0xD0, 0x10]);
+        config.quirks.clip_sprites = true;
+        config.quirks.collision_rows = true;
+        screen.set_hires(true);
+
+        // 4 rows collide and 10 rows are clipped at the bottom
+        cpu.i = 4;
+        cpu.v[0] = 0;
+        cpu.v[1] = 58;
+        for y in 60..64 {
+            screen.set_pixel(3, y);
+        }
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0xF], 14);
+
+        // Lores mode keeps the single collision flag
+        screen.set_hires(false);
+        cpu.v[1] = 0;
+        screen.set_pixel(0, 0);
+        screen.set_pixel(0, 1);
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0xF], 1);
+    }
+
+    #[test]
+    fn quirk_display_wait() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        let mut memory: [u8; 6] = [0xD0, 0x01, 0x60, 0x01, 0x80, 0x00];
+        config.quirks.display_wait = true;
+
+        cpu.i = 4;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(cpu.waiting_vblank);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 2);
+
+        cpu.waiting_vblank = false;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0x0], 0x01);
+    }
+
     #[test]
     fn test_bcd() {
         let (bcd2, bcd1, bcd0) = bcd(123);
@@ -712,4 +1546,102 @@ mod tests {
         assert_eq!(bcd1, 5);
         assert_eq!(bcd0, 1);
     }
+
+    #[test]
+    fn out_of_bounds_memory() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let opcodes = [0xD015, 0xF033, 0xF355, 0xF365];
+
+        for opcode in opcodes {
+            let mut memory = [0; 8];
+            memory[..2].copy_from_slice(&u16::to_be_bytes(opcode));
+            cpu.pc = 0;
+            cpu.i = 6;
+            cpu.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+
+            let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+            assert!(
+                matches!(
+                    e.root(),
+                    ChipError::AddressOutOfBounds {
+                        address: 8,
+                        limit: 8
+                    }
+                ),
+                "{opcode:#06x}"
+            );
+            // Nothing is written when any of the addresses is out of bounds
+            assert_eq!(memory[2..], [0; 6]);
+            assert_eq!(cpu.v[..4], [1, 2, 3, 4]);
+            assert_eq!(cpu.i, 6);
+        }
+        assert!(!screen.get_pixel(1, 2));
+
+        cpu.pc = 0;
+        cpu.i = u16::MAX;
+        let mut memory = [0xF0, 0x65, 0x00, 0x00];
+        let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+        assert!(matches!(e.root(), ChipError::AddressOutOfBounds { .. }));
+    }
+
+    #[test]
+    fn out_of_bounds_memory_xo_chip() {
+        let (mut cpu, mut screen, config) = xo_chip_setup();
+
+        for opcode in [0x5012, 0x5013, 0xF002] {
+            let mut memory = [0; 8];
+            memory[..2].copy_from_slice(&u16::to_be_bytes(opcode));
+            cpu.pc = 0;
+            cpu.i = 7;
+
+            let e = cpu.step(&mut memory, &mut screen, &config).unwrap_err();
+            assert!(
+                matches!(e.root(), ChipError::AddressOutOfBounds { .. }),
+                "{opcode:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn memory_access_wrap() {
+        let (mut cpu, mut screen, mut config) = test_setup();
+        config.memory_access = MemoryAccess::Wrap;
+        let mut memory: [u8; 8] = [0xF0, 0x33, 0xF0, 0x65, 0xD0, 0x13, 0x00, 0x00];
+
+        cpu.v[0] = 123;
+        cpu.i = 7;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(memory, [0x02, 0x03, 0xF0, 0x65, 0xD0, 0x13, 0x00, 0x01]);
+
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.v[0], 0x01);
+
+        cpu.v[0] = 0;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert!(screen.get_pixel(7, 0));
+        assert!(screen.get_pixel(6, 1));
+        assert!(!screen.get_pixel(7, 1));
+        assert!(screen.get_pixel(6, 2));
+        assert!(screen.get_pixel(7, 2));
+    }
+
+    #[test]
+    fn unchecked_register_values() {
+        let (mut cpu, mut screen, config) = test_setup();
+        let mut memory: [u8; 8] = [0xF0, 0x1E, 0xF0, 0x29, 0xE0, 0x9E, 0x00, 0x00];
+
+        cpu.i = u16::MAX;
+        cpu.v[0] = 2;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i, 1);
+
+        // Only the low nibble of VX selects the character and key
+        cpu.v[0] = 0xF2;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.i as usize, FONT_BASE_ADDRESS + 10);
+
+        cpu.keypad[0x2] = true;
+        cpu.step(&mut memory, &mut screen, &config).unwrap();
+        assert_eq!(cpu.pc, 8);
+    }
 }
@@ -0,0 +1,47 @@
+use std::fmt;
+
+use crate::Instruction;
+
+/// A snapshot of the registers of the [Cpu](crate::Cpu).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: usize,
+    pub sp: usize,
+    pub timer_delay: u8,
+    pub timer_sound: u8,
+}
+
+/// Describes a single instruction executed by [`Cpu::step`](crate::Cpu::step), passed to
+/// the hook set with [`Cpu::set_trace_hook`](crate::Cpu::set_trace_hook).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trace {
+    /// The address the instruction was fetched from.
+    pub pc: usize,
+    /// The first two bytes of the instruction.
+    pub opcode: u16,
+    /// The decoded instruction.
+    pub instruction: Instruction,
+    /// The registers before the instruction was executed.
+    pub before: Registers,
+    /// The registers after the instruction was executed.
+    pub after: Registers,
+}
+
+/// Formats the trace as a single line holding the address, the opcode, the instruction
+/// and the registers after it was executed, which makes trace logs easy to diff.
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instruction = self.instruction.to_string();
+        write!(f, "{:04X}: {:04X} {instruction:<16}", self.pc, self.opcode)?;
+        for (x, value) in self.after.v.iter().enumerate() {
+            write!(f, " V{x:X}={value:02X}")?;
+        }
+        write!(
+            f,
+            " I={:04X} SP={:X} DT={:02X} ST={:02X}",
+            self.after.i, self.after.sp, self.after.timer_delay, self.after.timer_sound
+        )
+    }
+}
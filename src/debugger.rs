@@ -0,0 +1,396 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use crate::bus::Bus;
+use crate::errors::ChipError;
+use crate::{Chip8, Instruction};
+
+const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+
+/// Explains why the [Debugger] stopped executing instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested instructions were executed without triggering anything.
+    Stepped,
+    /// The program counter reached a breakpoint.
+    Breakpoint { address: usize },
+    /// An instruction read from a watched address.
+    MemoryRead { address: usize },
+    /// An instruction wrote to a watched address.
+    MemoryWrite { address: usize, old: u8, new: u8 },
+    /// An instruction changed the value of a watched register.
+    RegisterChanged { register: u8, old: u8, new: u8 },
+    /// The CPU was halted by the Super-Chip 00FD instruction.
+    Halted,
+    /// The step limit was reached before the run finished.
+    StepLimit,
+}
+
+/// Wraps [`Chip8::step`] with breakpoints, watchpoints and stepping over and out of subroutines.
+///
+/// Instructions run on the same clock as [`Chip8::tick`] and [`Chip8::run_cycles`], so the
+/// timers tick at the end of every frame no matter how the machine is stepped.
+///
+/// Memory watchpoints are checked against the memory each instruction actually read and
+/// wrote, including the accesses of registered opcodes and unknown opcode handlers.
+/// Instruction fetches do not trigger them.
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    read_watchpoints: BTreeSet<usize>,
+    write_watchpoints: BTreeSet<usize>,
+    register_watchpoints: BTreeSet<u8>,
+    /// The maximum amount of instructions executed by a single [`Debugger::run`],
+    /// [`Debugger::step_over`] or [`Debugger::step_out`] call.
+    pub step_limit: usize,
+    accesses: MemoryAccesses,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            read_watchpoints: BTreeSet::new(),
+            write_watchpoints: BTreeSet::new(),
+            register_watchpoints: BTreeSet::new(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            accesses: MemoryAccesses::default(),
+        }
+    }
+}
+
+impl Debugger {
+    /// Stop whenever the program counter reaches the address.
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Remove a breakpoint. Returns false if there was no breakpoint at the address.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Stop whenever an instruction reads from the address.
+    pub fn watch_read(&mut self, address: usize) {
+        self.read_watchpoints.insert(address);
+    }
+
+    /// Stop whenever an instruction writes to the address.
+    pub fn watch_write(&mut self, address: usize) {
+        self.write_watchpoints.insert(address);
+    }
+
+    /// Stop whenever an instruction changes the value of the register (0x0 - 0xF).
+    pub fn watch_register(&mut self, register: u8) {
+        self.register_watchpoints.insert(register & 0xF);
+    }
+
+    /// Remove every breakpoint and watchpoint.
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.read_watchpoints.clear();
+        self.write_watchpoints.clear();
+        self.register_watchpoints.clear();
+    }
+
+    /// Execute a single instruction and report what it triggered.
+    pub fn step(&mut self, chip: &mut Chip8) -> Result<StopReason, ChipError> {
+        if chip.cpu.halted {
+            return Ok(StopReason::Halted);
+        }
+
+        let old_v = chip.cpu.v;
+        self.accesses.reads.clear();
+        self.accesses.writes.clear();
+        chip.advance_with(Some(&mut self.accesses))?;
+
+        let writes = self.accesses.writes.iter();
+        let mut watched_writes =
+            writes.filter(|(address, ..)| self.write_watchpoints.contains(address));
+        if let Some(&(address, old, new)) = watched_writes.next() {
+            return Ok(StopReason::MemoryWrite { address, old, new });
+        }
+        let mut reads = self.accesses.reads.iter();
+        if let Some(&address) = reads.find(|address| self.read_watchpoints.contains(address)) {
+            return Ok(StopReason::MemoryRead { address });
+        }
+        for &register in &self.register_watchpoints {
+            let (old, new) = (old_v[register as usize], chip.cpu.v[register as usize]);
+            if old != new {
+                return Ok(StopReason::RegisterChanged { register, old, new });
+            }
+        }
+        if chip.cpu.halted {
+            return Ok(StopReason::Halted);
+        }
+        if self.breakpoints.contains(&chip.cpu.pc) {
+            return Ok(StopReason::Breakpoint {
+                address: chip.cpu.pc,
+            });
+        }
+
+        Ok(StopReason::Stepped)
+    }
+
+    /// Execute instructions until a breakpoint or watchpoint is triggered.
+    pub fn run(&mut self, chip: &mut Chip8) -> Result<StopReason, ChipError> {
+        self.run_until(chip, |_| false)
+    }
+
+    /// Execute a single instruction. When it is a 2NNN subroutine call, keep executing
+    /// until the subroutine returns.
+    pub fn step_over(&mut self, chip: &mut Chip8) -> Result<StopReason, ChipError> {
+        let is_call = matches!(
+            Instruction::decode(&chip.memory, chip.cpu.pc),
+            Some(Instruction::Call(_))
+        );
+        if !is_call {
+            return self.step(chip);
+        }
+
+        let (return_addr, depth) = (chip.cpu.pc + 2, chip.cpu.sp);
+        self.run_until(chip, |chip| {
+            chip.cpu.pc == return_addr && chip.cpu.sp == depth
+        })
+    }
+
+    /// Execute instructions until the current subroutine returns with 00EE.
+    pub fn step_out(&mut self, chip: &mut Chip8) -> Result<StopReason, ChipError> {
+        let depth = chip.cpu.sp;
+        self.run_until(chip, |chip| chip.cpu.sp < depth)
+    }
+
+    fn run_until(
+        &mut self,
+        chip: &mut Chip8,
+        done: impl Fn(&Chip8) -> bool,
+    ) -> Result<StopReason, ChipError> {
+        for _ in 0..self.step_limit {
+            let reason = self.step(chip)?;
+            if reason != StopReason::Stepped || done(chip) {
+                return Ok(reason);
+            }
+        }
+
+        Ok(StopReason::StepLimit)
+    }
+}
+
+// The memory read and written by an instruction, in the order of the accesses
+#[derive(Clone, Default)]
+pub(crate) struct MemoryAccesses {
+    reads: Vec<usize>,
+    // The address with the old and new value
+    writes: Vec<(usize, u8, u8)>,
+}
+
+// Passes the accesses of the CPU on to the memory while recording them
+struct RecordingBus<'a> {
+    memory: &'a mut [u8],
+    // Reads only borrow the bus immutably
+    reads: RefCell<&'a mut Vec<usize>>,
+    writes: &'a mut Vec<(usize, u8, u8)>,
+}
+
+impl Bus for RecordingBus<'_> {
+    fn size(&self) -> usize {
+        self.memory.size()
+    }
+
+    fn read(&self, address: usize) -> Result<u8, ChipError> {
+        let value = self.memory.read(address)?;
+        self.reads.borrow_mut().push(address);
+
+        Ok(value)
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ChipError> {
+        let old = self.memory.read(address)?;
+        self.memory.write(address, value)?;
+        self.writes.push((address, old, value));
+
+        Ok(())
+    }
+
+    fn fetch(&self, address: usize) -> Result<u16, ChipError> {
+        self.memory.fetch(address)
+    }
+}
+
+impl Chip8 {
+    // Same as step, with the memory accessed by the instruction recorded
+    pub(crate) fn step_recorded(&mut self, accesses: &mut MemoryAccesses) -> Result<(), ChipError> {
+        let mut bus = RecordingBus {
+            memory: &mut self.memory,
+            reads: RefCell::new(&mut accesses.reads),
+            writes: &mut accesses.writes,
+        };
+        self.cpu.step(&mut bus, &mut self.screen, &self.config)?;
+        self.finish_step();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, MemoryAccess, Timing};
+
+    fn test_setup(rom: &[u8]) -> (Debugger, Chip8) {
+        let mut chip = Chip8::default();
+        chip.load_rom(rom).unwrap();
+
+        (Debugger::default(), chip)
+    }
+
+    #[test]
+    fn breakpoint() {
+        let (mut debugger, mut chip) = test_setup(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x00]);
+        debugger.add_breakpoint(0x204);
+
+        assert_eq!(debugger.step(&mut chip).unwrap(), StopReason::Stepped);
+        assert_eq!(
+            debugger.run(&mut chip).unwrap(),
+            StopReason::Breakpoint { address: 0x204 }
+        );
+        assert_eq!(chip.cpu.v[1], 0x02);
+
+        assert!(debugger.remove_breakpoint(0x204));
+        debugger.step_limit = 10;
+        assert_eq!(debugger.run(&mut chip).unwrap(), StopReason::StepLimit);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let rom = [0xA3, 0x00, 0x60, 0x07, 0xF0, 0x33, 0xF2, 0x65];
+        let (mut debugger, mut chip) = test_setup(&rom);
+        debugger.watch_write(0x302);
+        debugger.watch_read(0x301);
+        debugger.watch_register(0x1);
+
+        assert_eq!(
+            debugger.run(&mut chip).unwrap(),
+            StopReason::MemoryWrite {
+                address: 0x302,
+                old: 0,
+                new: 7
+            }
+        );
+        assert_eq!(
+            debugger.step(&mut chip).unwrap(),
+            StopReason::MemoryRead { address: 0x301 }
+        );
+
+        debugger.clear();
+        debugger.watch_register(0x2);
+        chip.cpu.pc = 0x206;
+        chip.cpu.v[0x2] = 0;
+        assert_eq!(
+            debugger.step(&mut chip).unwrap(),
+            StopReason::RegisterChanged {
+                register: 0x2,
+                old: 0,
+                new: 7
+            }
+        );
+    }
+
+    #[test]
+    fn step_over_and_out() {
+        // Call a subroutine at 0x206 that sets V0 and V1 before returning
+        let rom = [
+            0x22, 0x06, 0x62, 0x03, 0x12, 0x04, 0x60, 0x01, 0x61, 0x02, 0x00, 0xEE,
+        ];
+        let (mut debugger, mut chip) = test_setup(&rom);
+
+        assert_eq!(debugger.step_over(&mut chip).unwrap(), StopReason::Stepped);
+        assert_eq!(chip.cpu.pc, 0x202);
+        assert_eq!(chip.cpu.v[1], 0x02);
+
+        chip.cpu.pc = 0x200;
+        assert_eq!(debugger.step(&mut chip).unwrap(), StopReason::Stepped);
+        assert_eq!(chip.cpu.pc, 0x206);
+        assert_eq!(debugger.step_out(&mut chip).unwrap(), StopReason::Stepped);
+        assert_eq!(chip.cpu.pc, 0x202);
+        assert_eq!(chip.cpu.sp, 0);
+    }
+
+    #[test]
+    fn halted() {
+        let (mut debugger, mut chip) = test_setup(&[0x60, 0x01, 0x00, 0xFD]);
+
+        assert_eq!(debugger.run(&mut chip).unwrap(), StopReason::Halted);
+        assert_eq!(debugger.step(&mut chip).unwrap(), StopReason::Halted);
+    }
+
+    #[test]
+    fn timers() {
+        let (mut debugger, mut chip) = test_setup(&[0x12, 0x00]);
+        chip.cpu.timer_delay = 2;
+
+        for _ in 0..chip.config.tick_rate {
+            debugger.step(&mut chip).unwrap();
+        }
+        assert_eq!(chip.cpu.timer_delay, 1);
+    }
+
+    #[test]
+    fn same_timing_as_free_running() {
+        // Draw a sprite and set the delay timer in a loop
+        let rom = [0xD0, 0x15, 0x70, 0x01, 0xF0, 0x15, 0x12, 0x00];
+
+        for config in [Config::default(), Config::cosmac_vip()] {
+            for timing in [Timing::TickRate, Timing::CosmacVip] {
+                let config = Config {
+                    timing,
+                    ..config.clone()
+                };
+                let mut free = Chip8::new(config.clone());
+                free.load_rom(&rom).unwrap();
+                let mut debugged = free.clone();
+                let mut debugger = Debugger::default();
+
+                free.run_cycles(1000).unwrap();
+                for _ in 0..1000 {
+                    debugger.step(&mut debugged).unwrap();
+                }
+
+                assert_eq!(debugged.cpu.timer_delay, free.cpu.timer_delay);
+                assert_eq!(debugged.save_state(), free.save_state());
+            }
+        }
+    }
+
+    #[test]
+    fn watchpoints_follow_accesses() {
+        // Store V0 and V1 at I, then a registered opcode storing 0x42 at 0x300
+        let (mut debugger, mut chip) = test_setup(&[0xF1, 0x55, 0x03, 0x00]);
+        chip.config.memory_access = MemoryAccess::Wrap;
+        chip.cpu
+            .register_opcode(0xF000, 0x0000, |_, bus, _, opcode| {
+                bus.write((opcode & 0xFFF) as usize, 0x42)
+            });
+        debugger.watch_write(0x000);
+        debugger.watch_write(0x300);
+
+        // The second register wraps around to the start of the memory
+        chip.cpu.i = 0xFFF;
+        chip.cpu.v[..2].copy_from_slice(&[0x12, 0x34]);
+        assert_eq!(
+            debugger.step(&mut chip).unwrap(),
+            StopReason::MemoryWrite {
+                address: 0x000,
+                old: 0x00,
+                new: 0x34
+            }
+        );
+        assert_eq!(
+            debugger.step(&mut chip).unwrap(),
+            StopReason::MemoryWrite {
+                address: 0x300,
+                old: 0x00,
+                new: 0x42
+            }
+        );
+    }
+}
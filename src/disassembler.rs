@@ -0,0 +1,432 @@
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::cpu::opcodes::Opcode;
+
+/// The assembly syntax used to format an [Instruction].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syntax {
+    /// The syntax from Cowgod's Chip-8 technical reference, e.g. `LD V1, #2A`.
+    #[default]
+    Cowgod,
+    /// The syntax of the Octo assembler, e.g. `v1 := 0x2A`.
+    Octo,
+}
+
+/// A decoded CHIP-8, Super-Chip or XO-CHIP instruction.
+///
+/// `x` and `y` are register indices, `nn` a byte and `n` a nibble as found in the opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN - Call a machine code routine
+    MachineCall(u16),
+    /// 00CN - Scroll the screen down by N pixels
+    ScrollDown(u8),
+    /// 00DN - Scroll the screen up by N pixels
+    ScrollUp(u8),
+    /// 00E0 - Clear the screen
+    Clear,
+    /// 00EE - Return from a subroutine
+    Return,
+    /// 00FB - Scroll the screen right by 4 pixels
+    ScrollRight,
+    /// 00FC - Scroll the screen left by 4 pixels
+    ScrollLeft,
+    /// 00FD - Exit the interpreter
+    Exit,
+    /// 00FE - Switch to the low resolution mode
+    Lores,
+    /// 00FF - Switch to the high resolution mode
+    Hires,
+    /// 1NNN - Jump to NNN
+    Jump(u16),
+    /// 2NNN - Call the subroutine at NNN
+    Call(u16),
+    /// 3XNN - Skip the next instruction if VX == NN
+    SkipEqualByte { x: u8, nn: u8 },
+    /// 4XNN - Skip the next instruction if VX != NN
+    SkipNotEqualByte { x: u8, nn: u8 },
+    /// 5XY0 - Skip the next instruction if VX == VY
+    SkipEqual { x: u8, y: u8 },
+    /// 5XY2 - Store VX to VY in memory starting at I
+    StoreRange { x: u8, y: u8 },
+    /// 5XY3 - Load VX to VY from memory starting at I
+    RetrieveRange { x: u8, y: u8 },
+    /// 6XNN - VX = NN
+    SetByte { x: u8, nn: u8 },
+    /// 7XNN - VX += NN
+    AddByte { x: u8, nn: u8 },
+    /// 8XY0 - VX = VY
+    Set { x: u8, y: u8 },
+    /// 8XY1 - VX |= VY
+    Or { x: u8, y: u8 },
+    /// 8XY2 - VX &= VY
+    And { x: u8, y: u8 },
+    /// 8XY3 - VX ^= VY
+    Xor { x: u8, y: u8 },
+    /// 8XY4 - VX += VY
+    Add { x: u8, y: u8 },
+    /// 8XY5 - VX -= VY
+    Sub { x: u8, y: u8 },
+    /// 8XY6 - VX >>= 1
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7 - VX = VY - VX
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE - VX <<= 1
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0 - Skip the next instruction if VX != VY
+    SkipNotEqual { x: u8, y: u8 },
+    /// ANNN - I = NNN
+    SetI(u16),
+    /// BNNN - Jump to NNN + V0
+    JumpOffset(u16),
+    /// CXNN - VX = random & NN
+    Random { x: u8, nn: u8 },
+    /// DXYN - Draw an N pixels tall sprite at (VX, VY)
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E - Skip the next instruction if the key VX is pressed
+    SkipKey(u8),
+    /// EXA1 - Skip the next instruction if the key VX is not pressed
+    SkipNotKey(u8),
+    /// F000 NNNN - I = NNNN. The address is only known when decoded from memory.
+    LoadLong(Option<u16>),
+    /// FN01 - Select the drawing planes N
+    Plane(u8),
+    /// F002 - Load the audio pattern buffer from memory at I
+    Audio,
+    /// FX07 - VX = delay timer
+    GetDelay(u8),
+    /// FX0A - Wait for a key press and store it in VX
+    WaitKey(u8),
+    /// FX15 - Delay timer = VX
+    SetDelay(u8),
+    /// FX18 - Sound timer = VX
+    SetSound(u8),
+    /// FX1E - I += VX
+    AddI(u8),
+    /// FX29 - I = address of the font sprite for VX
+    Font(u8),
+    /// FX30 - I = address of the big font sprite for VX
+    BigFont(u8),
+    /// FX33 - Store the BCD of VX in memory at I
+    Bcd(u8),
+    /// FX3A - Audio pitch = VX
+    Pitch(u8),
+    /// FX55 - Store V0 to VX in memory starting at I
+    Store(u8),
+    /// FX65 - Load V0 to VX from memory starting at I
+    Retrieve(u8),
+    /// FX75 - Store V0 to VX in the RPL flags
+    StoreFlags(u8),
+    /// FX85 - Load V0 to VX from the RPL flags
+    RetrieveFlags(u8),
+    /// Any opcode that isn't a known instruction
+    Unknown(u16),
+}
+
+/// A single instruction of a disassembled program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// The memory address the instruction is located at.
+    pub address: usize,
+    /// The first two bytes of the instruction.
+    pub opcode: u16,
+    /// The decoded instruction.
+    pub instruction: Instruction,
+}
+
+impl From<u16> for Instruction {
+    fn from(value: u16) -> Self {
+        let opcode = Opcode::from(value);
+        let (x, y, n, nn, nnn) = (opcode.x, opcode.y, opcode.n, opcode.nn, opcode.nnn);
+
+        match (opcode.prefix, nn) {
+            (0x0, 0xE0) if x == 0 => Instruction::Clear,
+            (0x0, 0xEE) if x == 0 => Instruction::Return,
+            (0x0, 0xFB) if x == 0 => Instruction::ScrollRight,
+            (0x0, 0xFC) if x == 0 => Instruction::ScrollLeft,
+            (0x0, 0xFD) if x == 0 => Instruction::Exit,
+            (0x0, 0xFE) if x == 0 => Instruction::Lores,
+            (0x0, 0xFF) if x == 0 => Instruction::Hires,
+            (0x0, _) if x == 0 && y == 0xC => Instruction::ScrollDown(n),
+            (0x0, _) if x == 0 && y == 0xD => Instruction::ScrollUp(n),
+            (0x0, _) => Instruction::MachineCall(nnn),
+            (0x1, _) => Instruction::Jump(nnn),
+            (0x2, _) => Instruction::Call(nnn),
+            (0x3, _) => Instruction::SkipEqualByte { x, nn },
+            (0x4, _) => Instruction::SkipNotEqualByte { x, nn },
+            (0x5, _) if n == 0x0 => Instruction::SkipEqual { x, y },
+            (0x5, _) if n == 0x2 => Instruction::StoreRange { x, y },
+            (0x5, _) if n == 0x3 => Instruction::RetrieveRange { x, y },
+            (0x6, _) => Instruction::SetByte { x, nn },
+            (0x7, _) => Instruction::AddByte { x, nn },
+            (0x8, _) if n == 0x0 => Instruction::Set { x, y },
+            (0x8, _) if n == 0x1 => Instruction::Or { x, y },
+            (0x8, _) if n == 0x2 => Instruction::And { x, y },
+            (0x8, _) if n == 0x3 => Instruction::Xor { x, y },
+            (0x8, _) if n == 0x4 => Instruction::Add { x, y },
+            (0x8, _) if n == 0x5 => Instruction::Sub { x, y },
+            (0x8, _) if n == 0x6 => Instruction::ShiftRight { x, y },
+            (0x8, _) if n == 0x7 => Instruction::SubReverse { x, y },
+            (0x8, _) if n == 0xE => Instruction::ShiftLeft { x, y },
+            (0x9, _) if n == 0x0 => Instruction::SkipNotEqual { x, y },
+            (0xA, _) => Instruction::SetI(nnn),
+            (0xB, _) => Instruction::JumpOffset(nnn),
+            (0xC, _) => Instruction::Random { x, nn },
+            (0xD, _) => Instruction::Draw { x, y, n },
+            (0xE, 0x9E) => Instruction::SkipKey(x),
+            (0xE, 0xA1) => Instruction::SkipNotKey(x),
+            (0xF, 0x00) if x == 0 => Instruction::LoadLong(None),
+            (0xF, 0x01) => Instruction::Plane(x),
+            (0xF, 0x02) if x == 0 => Instruction::Audio,
+            (0xF, 0x07) => Instruction::GetDelay(x),
+            (0xF, 0x0A) => Instruction::WaitKey(x),
+            (0xF, 0x15) => Instruction::SetDelay(x),
+            (0xF, 0x18) => Instruction::SetSound(x),
+            (0xF, 0x1E) => Instruction::AddI(x),
+            (0xF, 0x29) => Instruction::Font(x),
+            (0xF, 0x30) => Instruction::BigFont(x),
+            (0xF, 0x33) => Instruction::Bcd(x),
+            (0xF, 0x3A) => Instruction::Pitch(x),
+            (0xF, 0x55) => Instruction::Store(x),
+            (0xF, 0x65) => Instruction::Retrieve(x),
+            (0xF, 0x75) => Instruction::StoreFlags(x),
+            (0xF, 0x85) => Instruction::RetrieveFlags(x),
+            _ => Instruction::Unknown(opcode.hex),
+        }
+    }
+}
+
+impl Instruction {
+    /// Decode the instruction at the address. The address of F000 NNNN is read from the
+    /// following two bytes. Returns None if the instruction doesn't fit in memory.
+    pub fn decode(memory: &(impl Bus + ?Sized), address: usize) -> Option<Instruction> {
+        let instruction = Instruction::from(memory.fetch(address).ok()?);
+
+        match instruction {
+            Instruction::LoadLong(_) => {
+                Some(Instruction::LoadLong(Some(memory.fetch(address + 2).ok()?)))
+            }
+            _ => Some(instruction),
+        }
+    }
+
+    /// The size of the instruction in bytes. Only F000 NNNN is 4 bytes long.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadLong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Format the instruction in the chosen assembly syntax.
+    pub fn mnemonic(&self, syntax: Syntax) -> String {
+        match syntax {
+            Syntax::Cowgod => self.cowgod(),
+            Syntax::Octo => self.octo(),
+        }
+    }
+
+    fn cowgod(&self) -> String {
+        match *self {
+            Instruction::MachineCall(nnn) => format!("SYS #{nnn:03X}"),
+            Instruction::ScrollDown(n) => format!("SCD {n}"),
+            Instruction::ScrollUp(n) => format!("SCU {n}"),
+            Instruction::Clear => "CLS".to_string(),
+            Instruction::Return => "RET".to_string(),
+            Instruction::ScrollRight => "SCR".to_string(),
+            Instruction::ScrollLeft => "SCL".to_string(),
+            Instruction::Exit => "EXIT".to_string(),
+            Instruction::Lores => "LOW".to_string(),
+            Instruction::Hires => "HIGH".to_string(),
+            Instruction::Jump(nnn) => format!("JP #{nnn:03X}"),
+            Instruction::Call(nnn) => format!("CALL #{nnn:03X}"),
+            Instruction::SkipEqualByte { x, nn } => format!("SE V{x:X}, #{nn:02X}"),
+            Instruction::SkipNotEqualByte { x, nn } => format!("SNE V{x:X}, #{nn:02X}"),
+            Instruction::SkipEqual { x, y } => format!("SE V{x:X}, V{y:X}"),
+            Instruction::StoreRange { x, y } => format!("SAVE V{x:X}, V{y:X}"),
+            Instruction::RetrieveRange { x, y } => format!("LOAD V{x:X}, V{y:X}"),
+            Instruction::SetByte { x, nn } => format!("LD V{x:X}, #{nn:02X}"),
+            Instruction::AddByte { x, nn } => format!("ADD V{x:X}, #{nn:02X}"),
+            Instruction::Set { x, y } => format!("LD V{x:X}, V{y:X}"),
+            Instruction::Or { x, y } => format!("OR V{x:X}, V{y:X}"),
+            Instruction::And { x, y } => format!("AND V{x:X}, V{y:X}"),
+            Instruction::Xor { x, y } => format!("XOR V{x:X}, V{y:X}"),
+            Instruction::Add { x, y } => format!("ADD V{x:X}, V{y:X}"),
+            Instruction::Sub { x, y } => format!("SUB V{x:X}, V{y:X}"),
+            Instruction::ShiftRight { x, y } => format!("SHR V{x:X}, V{y:X}"),
+            Instruction::SubReverse { x, y } => format!("SUBN V{x:X}, V{y:X}"),
+            Instruction::ShiftLeft { x, y } => format!("SHL V{x:X}, V{y:X}"),
+            Instruction::SkipNotEqual { x, y } => format!("SNE V{x:X}, V{y:X}"),
+            Instruction::SetI(nnn) => format!("LD I, #{nnn:03X}"),
+            Instruction::JumpOffset(nnn) => format!("JP V0, #{nnn:03X}"),
+            Instruction::Random { x, nn } => format!("RND V{x:X}, #{nn:02X}"),
+            Instruction::Draw { x, y, n } => format!("DRW V{x:X}, V{y:X}, {n}"),
+            Instruction::SkipKey(x) => format!("SKP V{x:X}"),
+            Instruction::SkipNotKey(x) => format!("SKNP V{x:X}"),
+            Instruction::LoadLong(Some(nnnn)) => format!("LD I, #{nnnn:04X}"),
+            Instruction::LoadLong(None) => "LD I, LONG".to_string(),
+            Instruction::Plane(n) => format!("PLANE {n}"),
+            Instruction::Audio => "AUDIO".to_string(),
+            Instruction::GetDelay(x) => format!("LD V{x:X}, DT"),
+            Instruction::WaitKey(x) => format!("LD V{x:X}, K"),
+            Instruction::SetDelay(x) => format!("LD DT, V{x:X}"),
+            Instruction::SetSound(x) => format!("LD ST, V{x:X}"),
+            Instruction::AddI(x) => format!("ADD I, V{x:X}"),
+            Instruction::Font(x) => format!("LD F, V{x:X}"),
+            Instruction::BigFont(x) => format!("LD HF, V{x:X}"),
+            Instruction::Bcd(x) => format!("LD B, V{x:X}"),
+            Instruction::Pitch(x) => format!("PITCH V{x:X}"),
+            Instruction::Store(x) => format!("LD [I], V{x:X}"),
+            Instruction::Retrieve(x) => format!("LD V{x:X}, [I]"),
+            Instruction::StoreFlags(x) => format!("LD R, V{x:X}"),
+            Instruction::RetrieveFlags(x) => format!("LD V{x:X}, R"),
+            Instruction::Unknown(opcode) => format!("DW #{opcode:04X}"),
+        }
+    }
+
+    fn octo(&self) -> String {
+        match *self {
+            Instruction::MachineCall(nnn) => format!("0x{:02X} 0x{:02X}", nnn >> 8, nnn & 0xFF),
+            Instruction::ScrollDown(n) => format!("scroll-down {n}"),
+            Instruction::ScrollUp(n) => format!("scroll-up {n}"),
+            Instruction::Clear => "clear".to_string(),
+            Instruction::Return => "return".to_string(),
+            Instruction::ScrollRight => "scroll-right".to_string(),
+            Instruction::ScrollLeft => "scroll-left".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::Lores => "lores".to_string(),
+            Instruction::Hires => "hires".to_string(),
+            Instruction::Jump(nnn) => format!("jump 0x{nnn:03X}"),
+            Instruction::Call(nnn) => format!(":call 0x{nnn:03X}"),
+            Instruction::SkipEqualByte { x, nn } => format!("if v{x:x} != 0x{nn:02X} then"),
+            Instruction::SkipNotEqualByte { x, nn } => format!("if v{x:x} == 0x{nn:02X} then"),
+            Instruction::SkipEqual { x, y } => format!("if v{x:x} != v{y:x} then"),
+            Instruction::StoreRange { x, y } => format!("save v{x:x} - v{y:x}"),
+            Instruction::RetrieveRange { x, y } => format!("load v{x:x} - v{y:x}"),
+            Instruction::SetByte { x, nn } => format!("v{x:x} := 0x{nn:02X}"),
+            Instruction::AddByte { x, nn } => format!("v{x:x} += 0x{nn:02X}"),
+            Instruction::Set { x, y } => format!("v{x:x} := v{y:x}"),
+            Instruction::Or { x, y } => format!("v{x:x} |= v{y:x}"),
+            Instruction::And { x, y } => format!("v{x:x} &= v{y:x}"),
+            Instruction::Xor { x, y } => format!("v{x:x} ^= v{y:x}"),
+            Instruction::Add { x, y } => format!("v{x:x} += v{y:x}"),
+            Instruction::Sub { x, y } => format!("v{x:x} -= v{y:x}"),
+            Instruction::ShiftRight { x, y } => format!("v{x:x} >>= v{y:x}"),
+            Instruction::SubReverse { x, y } => format!("v{x:x} =- v{y:x}"),
+            Instruction::ShiftLeft { x, y } => format!("v{x:x} <<= v{y:x}"),
+            Instruction::SkipNotEqual { x, y } => format!("if v{x:x} == v{y:x} then"),
+            Instruction::SetI(nnn) => format!("i := 0x{nnn:03X}"),
+            Instruction::JumpOffset(nnn) => format!("jump0 0x{nnn:03X}"),
+            Instruction::Random { x, nn } => format!("v{x:x} := random 0x{nn:02X}"),
+            Instruction::Draw { x, y, n } => format!("sprite v{x:x} v{y:x} {n}"),
+            Instruction::SkipKey(x) => format!("if v{x:x} -key then"),
+            Instruction::SkipNotKey(x) => format!("if v{x:x} key then"),
+            Instruction::LoadLong(Some(nnnn)) => format!("i := long 0x{nnnn:04X}"),
+            Instruction::LoadLong(None) => "i := long".to_string(),
+            Instruction::Plane(n) => format!("plane {n}"),
+            Instruction::Audio => "audio".to_string(),
+            Instruction::GetDelay(x) => format!("v{x:x} := delay"),
+            Instruction::WaitKey(x) => format!("v{x:x} := key"),
+            Instruction::SetDelay(x) => format!("delay := v{x:x}"),
+            Instruction::SetSound(x) => format!("buzzer := v{x:x}"),
+            Instruction::AddI(x) => format!("i += v{x:x}"),
+            Instruction::Font(x) => format!("i := hex v{x:x}"),
+            Instruction::BigFont(x) => format!("i := bighex v{x:x}"),
+            Instruction::Bcd(x) => format!("bcd v{x:x}"),
+            Instruction::Pitch(x) => format!("pitch := v{x:x}"),
+            Instruction::Store(x) => format!("save v{x:x}"),
+            Instruction::Retrieve(x) => format!("load v{x:x}"),
+            Instruction::StoreFlags(x) => format!("saveflags v{x:x}"),
+            Instruction::RetrieveFlags(x) => format!("loadflags v{x:x}"),
+            Instruction::Unknown(opcode) => {
+                format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF)
+            }
+        }
+    }
+}
+
+/// Formats the instruction using the [`Syntax::Cowgod`] syntax.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.cowgod())
+    }
+}
+
+/// Disassemble a program located at the base address, such as a ROM or a region of memory.
+///
+/// Every two bytes are decoded as an instruction, except for F000 NNNN which takes four.
+/// Sprite data mixed with the code is decoded as instructions as well. A trailing odd
+/// byte is ignored.
+pub fn disassemble(data: &[u8], base_address: usize) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while let Ok(opcode) = data.fetch(offset) {
+        // A long load missing its address at the end of the data is left undecoded
+        let instruction = Instruction::decode(data, offset).unwrap_or(Instruction::from(opcode));
+        lines.push(Line {
+            address: base_address + offset,
+            opcode,
+            instruction,
+        });
+        offset += instruction.size();
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(Instruction::from(0x00E0), Instruction::Clear);
+        assert_eq!(Instruction::from(0x00C4), Instruction::ScrollDown(4));
+        assert_eq!(Instruction::from(0x0123), Instruction::MachineCall(0x123));
+        assert_eq!(
+            Instruction::from(0x812E),
+            Instruction::ShiftLeft { x: 1, y: 2 }
+        );
+        assert_eq!(Instruction::from(0x8128), Instruction::Unknown(0x8128));
+        assert_eq!(
+            Instruction::from(0xD12F),
+            Instruction::Draw { x: 1, y: 2, n: 15 }
+        );
+        assert_eq!(Instruction::from(0xF000), Instruction::LoadLong(None));
+        assert_eq!(
+            Instruction::decode(&[0xF0, 0x00, 0x12, 0x34], 0),
+            Some(Instruction::LoadLong(Some(0x1234)))
+        );
+        assert_eq!(Instruction::decode(&[0xF0, 0x00, 0x12], 0), None);
+    }
+
+    #[test]
+    fn mnemonics() {
+        let instruction = Instruction::from(0x6A2B);
+        assert_eq!(instruction.to_string(), "LD VA, #2B");
+        assert_eq!(instruction.mnemonic(Syntax::Octo), "va := 0x2B");
+
+        let instruction = Instruction::from(0x3105);
+        assert_eq!(instruction.to_string(), "SE V1, #05");
+        assert_eq!(instruction.mnemonic(Syntax::Octo), "if v1 != 0x05 then");
+
+        let instruction = Instruction::from(0xF233);
+        assert_eq!(instruction.to_string(), "LD B, V2");
+        assert_eq!(instruction.mnemonic(Syntax::Octo), "bcd v2");
+    }
+
+    #[test]
+    fn disassemble_rom() {
+        let rom = [0x00, 0xE0, 0xF0, 0x00, 0xAB, 0xCD, 0x12, 0x00, 0xFF];
+        let lines = disassemble(&rom, 0x200);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].address, 0x202);
+        assert_eq!(lines[1].instruction, Instruction::LoadLong(Some(0xABCD)));
+        assert_eq!(lines[2].address, 0x206);
+        assert_eq!(lines[2].opcode, 0x1200);
+        assert_eq!(lines[2].instruction.to_string(), "JP #200");
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use thiserror::Error;
 
 /// The error types used by the interpreter
@@ -18,4 +20,91 @@ pub enum ChipError {
     /// Thrown by the CPU when attempting to execute an unknown opcode
     #[error("The opcode {:#06x} is not implemented", .opcode)]
     OpcodeNotImplemented { opcode: u16 },
+
+    /// Thrown when loading a save state written in a format version that isn't supported
+    #[error(
+        "The save state version {version} is not supported. Supported versions: (1, {supported})"
+    )]
+    UnsupportedSaveStateVersion { version: u16, supported: u16 },
+
+    /// Thrown when loading data that is not a valid save state
+    #[error("Invalid save state: {0}")]
+    InvalidSaveState(String),
+
+    /// Thrown by the assembler when the source can't be assembled
+    #[error("Assembly error on line {line}: {message}")]
+    AssemblyError { line: usize, message: String },
+
+    /// Thrown when a key outside of the keypad is pressed or released
+    #[error("The key {0:#x} is not on the keypad. The keys are: (0x0, 0xf)")]
+    InvalidKey(u8),
+
+    /// Thrown when a buffer doesn't have the size required to hold the rendered screen
+    #[error("The buffer holds {size} pixels but {required} are required")]
+    InvalidBufferSize { size: usize, required: usize },
+
+    /// Thrown when encoding the screen as an image with a scale of zero
+    #[error("The scale {0} is invalid. The smallest scale is 1")]
+    InvalidScale(usize),
+
+    /// Wraps every error raised by [`Cpu::step`](crate::Cpu::step) with the state of the
+    /// machine at the failing instruction
+    #[error("{error} ({context})")]
+    Fault {
+        context: ErrorContext,
+        error: Box<ChipError>,
+    },
+}
+
+impl ChipError {
+    /// The underlying error, without the context added by [`Cpu::step`](crate::Cpu::step).
+    pub fn root(&self) -> &ChipError {
+        match self {
+            ChipError::Fault { error, .. } => error.root(),
+            e => e,
+        }
+    }
+
+    /// The state of the machine at the failing instruction, if the error was raised by
+    /// [`Cpu::step`](crate::Cpu::step).
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ChipError::Fault { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
+
+/// The state of the machine when an instruction failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The address of the failing instruction.
+    pub pc: usize,
+    /// The failing instruction, or None when it couldn't be fetched.
+    pub opcode: Option<u16>,
+    /// The I register.
+    pub i: u16,
+    /// How many subroutine calls are on the stack.
+    pub stack_depth: usize,
+    /// The addresses of the 2NNN instructions that led to the failing instruction,
+    /// the most recent call first.
+    pub backtrace: Vec<usize>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PC={:04X} ", self.pc)?;
+        match self.opcode {
+            Some(opcode) => write!(f, "opcode={opcode:04X}")?,
+            None => write!(f, "opcode=----")?,
+        }
+        write!(f, " I={:04X} stack depth={}", self.i, self.stack_depth)?;
+
+        for (n, address) in self.backtrace.iter().enumerate() {
+            let separator = if n == 0 { ", called from" } else { " <-" };
+            write!(f, "{separator} {address:04X}")?;
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,201 @@
+use crate::errors::ChipError;
+use crate::screen::{Palette, PixelFormat};
+use crate::Screen;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// The largest amount of data in an uncompressed deflate block
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+impl Screen {
+    /// Encode the display as a binary PPM (P6) image, where every pixel of the display
+    /// becomes a square of `scale` by `scale` pixels. Returns a [`ChipError::InvalidScale`]
+    /// if the scale is zero.
+    pub fn to_ppm(&self, scale: usize, palette: &Palette) -> Result<Vec<u8>, ChipError> {
+        let (width, height, rgb) = self.to_rgb(scale, palette)?;
+
+        let mut data = format!("P6\n{width} {height}\n255\n").into_bytes();
+        data.extend_from_slice(&rgb);
+
+        Ok(data)
+    }
+
+    /// Encode the display as a PNG image, where every pixel of the display becomes a
+    /// square of `scale` by `scale` pixels. Returns a [`ChipError::InvalidScale`] if the
+    /// scale is zero.
+    ///
+    /// The image data is stored without compression, which keeps the encoder small at the
+    /// cost of larger files.
+    pub fn to_png(&self, scale: usize, palette: &Palette) -> Result<Vec<u8>, ChipError> {
+        let (width, height, rgb) = self.to_rgb(scale, palette)?;
+
+        // Every row starts with the filter type, 0 for none
+        let mut raw = Vec::with_capacity(rgb.len() + height);
+        for row in rgb.chunks_exact(width * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut data = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut data, b"IHDR", &header);
+        write_chunk(&mut data, b"IDAT", &zlib_stored(&raw));
+        write_chunk(&mut data, b"IEND", &[]);
+
+        Ok(data)
+    }
+
+    // The scaled display as rows of RGB bytes
+    fn to_rgb(
+        &self,
+        scale: usize,
+        palette: &Palette,
+    ) -> Result<(usize, usize, Vec<u8>), ChipError> {
+        // An empty image can't be encoded
+        if scale == 0 {
+            return Err(ChipError::InvalidScale(scale));
+        }
+
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut pixels = vec![0; width * height];
+        self.render(&mut pixels, scale, palette, PixelFormat::Rgba)?;
+
+        let rgb = pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [r, g, b, _] = pixel.to_be_bytes();
+                [r, g, b]
+            })
+            .collect();
+
+        Ok((width, height, rgb))
+    }
+}
+
+fn write_chunk(data: &mut Vec<u8>, tag: &[u8; 4], chunk: &[u8]) {
+    data.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+    let start = data.len();
+    data.extend_from_slice(tag);
+    data.extend_from_slice(chunk);
+    let crc = crc32(&data[start..]);
+    data.extend_from_slice(&crc.to_be_bytes());
+}
+
+// A zlib stream holding the data in uncompressed deflate blocks
+fn zlib_stored(raw: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window and no preset dictionary
+    let mut data = vec![0x78, 0x01];
+
+    let mut blocks = raw.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        data.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        data.push(last as u8);
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend_from_slice(&(!len).to_le_bytes());
+        data.extend_from_slice(block);
+    }
+
+    data.extend_from_slice(&adler32(raw).to_be_bytes());
+
+    data
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn ppm() {
+        let mut screen = Screen::default();
+        screen.set_pixel(1, 0);
+
+        let ppm = screen
+            .to_ppm(1, &Palette::two_colors(0x000000, 0x123456))
+            .unwrap();
+        let header = b"P6\n64 32\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 64 * 32 * 3);
+        assert_eq!(
+            ppm[header.len()..header.len() + 6],
+            [0, 0, 0, 0x12, 0x34, 0x56]
+        );
+    }
+
+    #[test]
+    fn png() {
+        let mut screen = Screen::default();
+        screen.set_hires(true);
+        screen.set_pixel(0, 0);
+
+        let png = screen.to_png(2, &Palette::default()).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 1, 0, 0, 0, 0, 128]);
+        assert_eq!(
+            png[png.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+
+        // 128 rows of a filter byte and 256 RGB pixels split into 2 stored blocks
+        let raw_len = 128 * (1 + 256 * 3);
+        let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        assert_eq!(idat_len, 2 + 2 * 5 + raw_len + 4);
+        assert_eq!(png[41..48], [0x78, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x00]);
+        assert_eq!(png[48..52], [0x00, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn zero_scale() {
+        let screen = Screen::default();
+        let palette = Palette::default();
+
+        assert!(matches!(
+            screen.to_ppm(0, &palette),
+            Err(ChipError::InvalidScale(0))
+        ));
+        assert!(matches!(
+            screen.to_png(0, &palette),
+            Err(ChipError::InvalidScale(0))
+        ));
+    }
+}
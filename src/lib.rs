@@ -44,26 +44,50 @@
 //! [here]: https://github.com/overthemil/schip8-macroquad
 //! [anyhow]: https://crates.io/crates/anyhow/
 
+mod assembler;
+mod audio;
+mod bus;
+mod clock;
 mod config;
 mod cpu;
+mod debugger;
+mod disassembler;
 mod errors;
+mod image;
 mod memory;
+mod random;
+mod rewind;
 mod screen;
-
-pub use config::Config;
-pub use cpu::Cpu;
-pub use errors::ChipError;
-pub use screen::Screen;
-
-const MEMORY_SIZE: usize = 4096;
+mod state;
+mod timing;
+
+pub use assembler::assemble;
+pub use audio::{Audio, Waveform};
+pub use bus::Bus;
+pub use config::{
+    Config, Font, MemoryAccess, MemoryIncrement, Quirks, Timing, UnknownOpcode, Variant,
+    CHIP8_MEMORY_SIZE, XO_CHIP_MEMORY_SIZE,
+};
+pub use cpu::{Cpu, OpcodeHandler, Registers, Trace, TraceHook, UnknownOpcodeHandler};
+pub use debugger::{Debugger, StopReason};
+pub use disassembler::{disassemble, Instruction, Line, Syntax};
+pub use errors::{ChipError, ErrorContext};
+pub use random::Random;
+pub use screen::{Palette, PixelFormat, Rect, Screen};
+pub use state::SAVE_STATE_VERSION;
+
+use clock::Clock;
+use rewind::RewindBuffer;
 
 /// Represents the CHIP-8 VM that acts as the interpreter.
+#[derive(Clone)]
 pub struct Chip8 {
     /// The full memory of the machine.
     ///
     /// The whole range is readable and writable and acts as RAM. The font and rom
     /// data are loaded to specific regions in this memory, typically in the lower addresses.
-    pub memory: [u8; MEMORY_SIZE],
+    /// Its size is set by [`Config::memory_size`].
+    pub memory: Vec<u8>,
     /// The display representing the pixels written to by the CPU.
     pub screen: Screen,
     /// Changes various settings of the interpreter. Ability to change the tick rate to
@@ -71,37 +95,73 @@ pub struct Chip8 {
     pub config: Config,
     /// The CPU containing the core of the interpreter.
     pub cpu: Cpu,
+    /// Renders the sound timer tone with [`Chip8::render_audio`].
+    pub audio: Audio,
     rom: Vec<u8>,
+    rewind: RewindBuffer,
+    clock: Clock,
 }
 
 impl Chip8 {
     /// Create a new CHIP-8 interpreter with a custom [Config].
+    ///
+    /// Use one of the presets such as [`Config::cosmac_vip`] or [`Config::schip_modern`]
+    /// to match the platform a ROM was written for.
     pub fn new(config: Config) -> Self {
         let mut c8 = Chip8 {
+            memory: vec![0; config.memory_size],
             config,
             ..Default::default()
         };
         c8.load_default_font();
         c8.cpu.pc = c8.config.rom_base_addr;
+        c8.cpu.random = c8.config.seed.map(Random::new).unwrap_or_default();
 
         c8
     }
 
     /// Performs a single Fetch-Decode-Execute cycle in the [Cpu].
     pub fn step(&mut self) -> Result<(), ChipError> {
-        self.cpu.step(&mut self.memory, &mut self.screen)?;
+        self.cpu
+            .step(&mut self.memory, &mut self.screen, &self.config)?;
+        self.finish_step();
+
+        Ok(())
+    }
+
+    /// Performs a single cycle like [`Chip8::step`], accessing the memory through the bus
+    /// instead of [`Chip8::memory`]. The bus can map devices into the address space, it
+    /// isn't part of save states or the frames recorded for [`Chip8::rewind`].
+    pub fn step_with<B: Bus>(&mut self, bus: &mut B) -> Result<(), ChipError> {
+        self.cpu.step(bus, &mut self.screen, &self.config)?;
+        self.finish_step();
 
         Ok(())
     }
 
+    // Counts the instruction that was just executed and records the sound it started
+    pub(crate) fn finish_step(&mut self) {
+        if self.config.timing == Timing::TickRate {
+            self.clock.cycles += 1;
+        }
+        self.record_sound();
+    }
+
     /// Execute a full render cycle. At 60fps, this should be executed 60 times per second.
     ///
-    /// The amount of steps that occurs in each render cycle is determined by the tick rate.
+    /// The amount of steps that occurs in each render cycle is determined by the tick rate,
+    /// or by the time the instructions took with [`Timing::CosmacVip`].
+    /// Use [`Chip8::run_for`] instead when the frontend doesn't render at 60fps.
+    /// The state at the end of the cycle is recorded for [`Chip8::rewind`] when enabled.
     pub fn tick(&mut self) -> Result<(), ChipError> {
-        for _ in 0..self.config.tick_rate {
-            self.step()?;
-        }
+        self.run_frame()
+    }
 
+    // Ticks the timers and releases a CPU waiting for the display at the end of a frame
+    pub(crate) fn end_frame(&mut self) {
+        self.cpu.waiting_vblank = false;
+        self.cpu.key_presses = 0;
+        self.cpu.key_releases = 0;
         if self.cpu.timer_delay > 0 {
             self.cpu.timer_delay -= 1;
         }
@@ -109,15 +169,21 @@ impl Chip8 {
             self.cpu.timer_sound -= 1;
         }
 
-        Ok(())
+        self.record_sound();
+        self.record_rewind_frame();
     }
 
     /// Sets the machine as if newly created. Any changed configs and loaded ROMs persist.
+    ///
+    /// The random number generator is seeded again from [`Config::seed`].
     pub fn reset(&mut self) {
-        self.screen.clear_screen();
+        self.screen = Screen::default();
         self.reset_memory();
         self.cpu.reset();
         self.cpu.pc = self.config.rom_base_addr;
+        self.clock.reset();
+        self.audio.reset();
+        self.cpu.random = self.config.seed.map(Random::new).unwrap_or_default();
     }
 
     /// Set any of the keys in the keypad (0x0 - 0xF) as pressed.
@@ -125,7 +191,29 @@ impl Chip8 {
         self.cpu.keypad = keys_pressed;
     }
 
-    /// Announces if a tone should be played.
+    /// Press a key of the keypad (0x0 - 0xF). The press is kept until the end of the next
+    /// frame for FX0A, even if the key is released before.
+    pub fn key_down(&mut self, key: u8) -> Result<(), ChipError> {
+        let bit = key_bit(key)?;
+        self.cpu.keypad[key as usize] = true;
+        self.cpu.key_presses |= bit;
+        self.cpu.key_releases &= !bit;
+
+        Ok(())
+    }
+
+    /// Release a key of the keypad (0x0 - 0xF). FX0A only resumes once the key it saw
+    /// pressed is released, unless [`Quirks::key_press_wait`] is set.
+    pub fn key_up(&mut self, key: u8) -> Result<(), ChipError> {
+        let bit = key_bit(key)?;
+        self.cpu.keypad[key as usize] = false;
+        self.cpu.key_releases |= bit;
+
+        Ok(())
+    }
+
+    /// Announces if a tone should be played. Use [`Chip8::render_audio`] to get the
+    /// samples of the tone instead.
     pub fn should_play_sound(&self) -> bool {
         self.cpu.timer_sound > 0
     }
@@ -133,12 +221,16 @@ impl Chip8 {
 
 impl Default for Chip8 {
     fn default() -> Self {
+        let config = Config::default();
         let mut c8 = Chip8 {
-            memory: [0; MEMORY_SIZE],
+            memory: vec![0; config.memory_size],
             screen: Screen::default(),
-            config: Config::default(),
+            config,
             cpu: Cpu::default(),
+            audio: Audio::default(),
             rom: Vec::new(),
+            rewind: RewindBuffer::default(),
+            clock: Clock::default(),
         };
         c8.load_default_font();
         c8.cpu.pc = c8.config.rom_base_addr;
@@ -146,3 +238,10 @@ impl Default for Chip8 {
         c8
     }
 }
+
+fn key_bit(key: u8) -> Result<u16, ChipError> {
+    match key {
+        0x0..=0xF => Ok(1 << key),
+        _ => Err(ChipError::InvalidKey(key)),
+    }
+}
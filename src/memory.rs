@@ -1,8 +1,8 @@
 use crate::errors::ChipError;
-use crate::MEMORY_SIZE;
-use crate::Chip8;
+use crate::{Chip8, Font};
 
 pub const FONT_BASE_ADDRESS: usize = 0x050;
+pub const BIG_FONT_BASE_ADDRESS: usize = 0x0A0;
 
 const FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -23,10 +23,48 @@ const FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+const COSMAC_FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
 impl Chip8 {
     /// Write a byte of data to the address specified.
     pub fn write(&mut self, address: usize, data: u8) -> Result<(), ChipError> {
-        if address >= MEMORY_SIZE {
+        if address >= self.memory.len() {
             return Err(ChipError::AddressOutOfBounds {
                 address,
                 limit: self.memory.len(),
@@ -40,7 +78,7 @@ impl Chip8 {
 
     /// Read a byte of data from the address specified.
     pub fn read(&self, address: usize) -> Result<u8, ChipError> {
-        if address >= MEMORY_SIZE {
+        if address >= self.memory.len() {
             return Err(ChipError::AddressOutOfBounds {
                 address,
                 limit: self.memory.len(),
@@ -53,7 +91,7 @@ impl Chip8 {
     /// Write an array of bytes to memory starting at the base address.
     pub fn load(&mut self, base_address: usize, data: &[u8]) -> Result<(), ChipError> {
         let end_address = base_address + data.len();
-        if (end_address) >= MEMORY_SIZE {
+        if (end_address) >= self.memory.len() {
             return Err(ChipError::AddressOutOfBounds {
                 address: end_address,
                 limit: self.memory.len(),
@@ -80,14 +118,30 @@ impl Chip8 {
         Ok(())
     }
 
-    /// Write the default font to memory.
+    /// Write an array of bytes to memory starting at the big font base address.
+    ///
+    /// The big font is used by the Super-Chip FX30 instruction and holds 8x10 sprites.
+    pub fn load_big_font(&mut self, font: &[u8]) -> Result<(), ChipError> {
+        self.load(BIG_FONT_BASE_ADDRESS, font)?;
+
+        Ok(())
+    }
+
+    /// Write the font selected in the [Config](crate::Config) and the big font to memory.
     pub fn load_default_font(&mut self) {
-        let _ = self.load_font(&FONT);
+        let font = match self.config.font {
+            Font::Cosmac => &COSMAC_FONT,
+            Font::SuperChip => &FONT,
+        };
+        let _ = self.load_font(font);
+        let _ = self.load_big_font(&BIG_FONT);
     }
 
     /// Set all values in memory to zero, reload default font and last loaded ROM.
+    ///
+    /// The memory is resized if [`Config::memory_size`](crate::Config::memory_size) changed.
     pub fn reset_memory(&mut self) {
-        self.memory = [0; MEMORY_SIZE];
+        self.memory = vec![0; self.config.memory_size];
         self.load_default_font();
         let rom_data = self.rom.clone();
         let _ = self.load(self.config.rom_base_addr, &rom_data);
@@ -99,6 +153,7 @@ mod tests {
     use super::*;
     use crate::Chip8;
     use crate::ChipError;
+    use crate::{Config, XO_CHIP_MEMORY_SIZE};
 
     #[test]
     fn write() {
@@ -117,6 +172,8 @@ mod tests {
 
         let val = c8.read(FONT_BASE_ADDRESS).unwrap();
         assert_eq!(val, 0xF0);
+        let val = c8.read(BIG_FONT_BASE_ADDRESS + 10).unwrap();
+        assert_eq!(val, 0x18);
         let val = c8.read(0x200).unwrap();
         assert_eq!(val, 0);
 
@@ -128,6 +185,26 @@ mod tests {
         assert!(matches!(e, Err(ChipError::AddressOutOfBounds { .. })));
     }
 
+    #[test]
+    fn font() {
+        let c8 = Chip8::new(Config::cosmac_vip());
+        assert_eq!(c8.memory[FONT_BASE_ADDRESS + 5], 0x60);
+
+        let c8 = Chip8::new(Config::schip_modern());
+        assert_eq!(c8.memory[FONT_BASE_ADDRESS + 5], 0x20);
+    }
+
+    #[test]
+    fn memory_size() {
+        let mut c8 = Chip8::new(Config::xo_chip());
+        assert_eq!(c8.memory.len(), XO_CHIP_MEMORY_SIZE);
+
+        c8.write(0xFFFF, 1).unwrap();
+        c8.config.memory_size = 4096;
+        c8.reset_memory();
+        assert_eq!(c8.memory.len(), 4096);
+    }
+
     #[test]
     fn load() {
         let mut c8 = Chip8::default();
@@ -0,0 +1,77 @@
+use rand::RngCore;
+
+/// The pseudo random number generator used by CXNN.
+///
+/// It is a SplitMix64 generator, so the whole state is a single number that is stored in
+/// save states and any seed is valid. It implements [`RngCore`] to work with the [rand]
+/// crate. The same seed always produces the same sequence, set it with
+/// [`Config::seed`](crate::Config::seed) to make runs reproducible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Create a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    /// The current state of the generator. A generator created with this state as its
+    /// seed produces the same sequence.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+/// Seeds the generator from the thread local random number generator.
+impl Default for Random {
+    fn default() -> Self {
+        Random::new(rand::random())
+    }
+}
+
+impl RngCore for Random {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        let sequence: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        assert_eq!(sequence, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+
+        let mut resumed = Random::new(a.state());
+        assert_eq!(resumed.next_u64(), a.next_u64());
+        assert_ne!(Random::new(43).next_u64(), Random::new(42).next_u64());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+
+use crate::errors::ChipError;
+use crate::Chip8;
+
+// A full save state is stored every this many frames, the rest only hold changes
+const KEYFRAME_INTERVAL: usize = 60;
+// Unchanged bytes shorter than this between two changes are merged into a single run
+const RUN_MERGE_GAP: usize = 8;
+// States are compared in chunks of this many bytes, skipping the unchanged chunks at once
+const DIFF_CHUNK_SIZE: usize = 64;
+
+/// Records the state of the machine at the end of every frame so it can be rewound.
+///
+/// Frames are stored as save states without the ROM. A full keyframe is kept periodically
+/// and every other frame only holds the bytes that changed since the frame before it.
+#[derive(Clone, Default)]
+pub(crate) struct RewindBuffer {
+    frames: VecDeque<Frame>,
+    // The full state of the most recent frame, used to compute the next delta
+    latest: Vec<u8>,
+    // The state of the frame being recorded, kept to reuse its allocation
+    next: Vec<u8>,
+}
+
+#[derive(Clone)]
+enum Frame {
+    Key(Vec<u8>),
+    Delta(Vec<Run>),
+}
+
+// A range of bytes starting at an offset that differs from the previous frame
+#[derive(Clone)]
+struct Run {
+    offset: usize,
+    bytes: Vec<u8>,
+}
+
+impl RewindBuffer {
+    /// Add the state of a new frame written into the buffer by `write`, dropping the oldest
+    /// frames to stay within the depth.
+    pub fn push(&mut self, write: impl FnOnce(&mut Vec<u8>), depth: usize) {
+        let mut state = std::mem::take(&mut self.next);
+        write(&mut state);
+
+        let since_key = self
+            .frames
+            .iter()
+            .rev()
+            .take_while(|frame| matches!(frame, Frame::Delta(_)))
+            .count();
+
+        let frame = match self.frames.is_empty()
+            || since_key + 1 >= KEYFRAME_INTERVAL
+            || state.len() != self.latest.len()
+        {
+            true => Frame::Key(state.clone()),
+            false => Frame::Delta(diff(&self.latest, &state)),
+        };
+        self.frames.push_back(frame);
+        self.next = std::mem::replace(&mut self.latest, state);
+
+        while self.frames.len() > depth {
+            self.drop_oldest();
+        }
+    }
+
+    /// The amount of frames stored.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Discard the most recent frames and return the state of the new most recent frame.
+    pub fn rewind(&mut self, frames: usize) -> Option<&[u8]> {
+        let len = self
+            .frames
+            .len()
+            .checked_sub(frames)
+            .filter(|&len| len > 0)?;
+        self.frames.truncate(len);
+        self.latest = self.reconstruct(len - 1);
+
+        Some(&self.latest)
+    }
+
+    /// Remove every recorded frame.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.latest.clear();
+        self.next.clear();
+    }
+
+    fn drop_oldest(&mut self) {
+        let Some(Frame::Key(mut state)) = self.frames.pop_front() else {
+            return;
+        };
+
+        // The oldest frame is always a keyframe, so the next one has to become one
+        if let Some(Frame::Delta(runs)) = self.frames.front() {
+            apply(&mut state, runs);
+            self.frames[0] = Frame::Key(state);
+        }
+    }
+
+    fn reconstruct(&self, index: usize) -> Vec<u8> {
+        let key = (0..=index)
+            .rev()
+            .find(|&i| matches!(self.frames[i], Frame::Key(_)))
+            .unwrap_or(0);
+
+        let mut state = Vec::new();
+        for frame in self.frames.range(key..=index) {
+            match frame {
+                Frame::Key(key_state) => state = key_state.clone(),
+                Frame::Delta(runs) => apply(&mut state, runs),
+            }
+        }
+
+        state
+    }
+}
+
+fn diff(old: &[u8], new: &[u8]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let chunks = old.chunks(DIFF_CHUNK_SIZE).zip(new.chunks(DIFF_CHUNK_SIZE));
+
+    for (chunk, (old_chunk, new_chunk)) in chunks.enumerate() {
+        if old_chunk == new_chunk {
+            continue;
+        }
+
+        for (index, (&old_byte, &new_byte)) in old_chunk.iter().zip(new_chunk).enumerate() {
+            if old_byte == new_byte {
+                continue;
+            }
+
+            let offset = chunk * DIFF_CHUNK_SIZE + index;
+            match runs.last_mut() {
+                Some(run) if offset - (run.offset + run.bytes.len()) < RUN_MERGE_GAP => {
+                    let end = run.offset + run.bytes.len();
+                    run.bytes.extend_from_slice(&new[end..=offset]);
+                }
+                _ => runs.push(Run {
+                    offset,
+                    bytes: vec![new_byte],
+                }),
+            }
+        }
+    }
+
+    runs
+}
+
+fn apply(state: &mut [u8], runs: &[Run]) {
+    for run in runs {
+        state[run.offset..run.offset + run.bytes.len()].copy_from_slice(&run.bytes);
+    }
+}
+
+impl Chip8 {
+    /// Step the machine back by the given amount of frames recorded by [`Chip8::tick`].
+    ///
+    /// Frames are only recorded when [`Config::rewind_depth`](crate::Config::rewind_depth)
+    /// is not zero. Returns how many frames were actually rewound, which is less than
+    /// requested when not enough frames have been recorded.
+    pub fn rewind(&mut self, frames: usize) -> Result<usize, ChipError> {
+        let frames = frames.min(self.rewind.len().saturating_sub(1));
+        if frames == 0 {
+            return Ok(0);
+        }
+
+        if let Some(state) = self.rewind.rewind(frames) {
+            let state = state.to_vec();
+            self.restore_state(&state)?;
+        }
+
+        Ok(frames)
+    }
+
+    /// Discard every frame recorded for rewinding.
+    pub fn clear_rewind(&mut self) {
+        self.rewind.clear();
+    }
+
+    pub(crate) fn record_rewind_frame(&mut self) {
+        if self.config.rewind_depth > 0 {
+            let mut rewind = std::mem::take(&mut self.rewind);
+            rewind.push(
+                |data| self.write_state(data, false),
+                self.config.rewind_depth,
+            );
+            self.rewind = rewind;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn rewind() {
+        let config = Config {
+            rewind_depth: 100,
+            tick_rate: 1,
+            ..Default::default()
+        };
+        let mut c8 = Chip8::new(config);
+        // Increment V0 and store it in memory forever
+        c8.load_rom(&[0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+            .unwrap();
+
+        for _ in 0..300 {
+            c8.tick().unwrap();
+        }
+        assert_eq!(c8.rewind.len(), 100);
+        assert_eq!(c8.cpu.v[0], 75);
+
+        assert_eq!(c8.rewind(3).unwrap(), 3);
+        assert_eq!(c8.cpu.v[0], 75);
+        assert_eq!(c8.memory[0x300], 74);
+        assert_eq!(c8.cpu.pc, 0x202);
+        assert_eq!(c8.rewind.len(), 97);
+
+        assert_eq!(c8.rewind(1000).unwrap(), 96);
+        assert_eq!(c8.cpu.v[0], 51);
+        assert_eq!(c8.memory[0x300], 50);
+        assert_eq!(c8.rewind(1).unwrap(), 0);
+
+        // The frames leave out the ROM, rewinding keeps the loaded one
+        let Some(Frame::Key(state)) = c8.rewind.frames.front() else {
+            panic!("the oldest frame is not a keyframe");
+        };
+        assert!(!state.windows(4).any(|tag| tag == b"ROM "));
+        c8.reset();
+        assert_eq!(
+            c8.memory[0x200..0x208],
+            [0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00]
+        );
+    }
+
+    #[test]
+    fn disabled() {
+        let mut c8 = Chip8::default();
+        c8.load_rom(&[0x12, 0x00]).unwrap();
+
+        c8.tick().unwrap();
+        c8.tick().unwrap();
+        assert_eq!(c8.rewind.len(), 0);
+        assert_eq!(c8.rewind(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn delta() {
+        let old = [
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+        ];
+        let mut new = old;
+        new[1] = 0xFF;
+        new[4] = 0xFF;
+        new[18] = 0xFF;
+
+        let runs = diff(&old, &new);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].bytes, [0xFF, 2, 3, 0xFF]);
+
+        let mut state = old;
+        apply(&mut state, &runs);
+        assert_eq!(state, new);
+    }
+}
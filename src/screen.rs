@@ -1,53 +1,424 @@
+use crate::errors::ChipError;
+
 const CHIP8_SCREEN_WIDTH: usize = 64;
 const CHIP8_SCREEN_HEIGHT: usize = 32;
+const SCHIP_SCREEN_WIDTH: usize = 128;
+const SCHIP_SCREEN_HEIGHT: usize = 64;
+const ALL_PLANES: u8 = 0b11;
+
+/// The layout of the channels in the pixels written by [`Screen::render`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 0xRRGGBBAA
+    #[default]
+    Rgba,
+    /// 0xAARRGGBB
+    Argb,
+}
+
+/// The colors of the pixels, indexed by [`Screen::get_pixel_color`].
+///
+/// Colors are written as 0xRRGGBB and are always opaque. CHIP-8 and Super-Chip programs
+/// only use the first two colors, XO-CHIP programs use all four.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; 4],
+}
+
+impl Palette {
+    /// A palette for programs drawing to a single plane.
+    pub fn two_colors(background: u32, foreground: u32) -> Self {
+        Palette {
+            colors: [background, foreground, foreground, foreground],
+        }
+    }
+
+    /// The default colors of the Octo XO-CHIP environment.
+    pub fn octo() -> Self {
+        Palette {
+            colors: [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+        }
+    }
+
+    // The color of the pixel in the pixel format
+    fn pixel(&self, color: u8, format: PixelFormat) -> u32 {
+        let rgb = self.colors[(color & ALL_PLANES) as usize] & 0xFFFFFF;
+        match format {
+            PixelFormat::Rgba => rgb << 8 | 0xFF,
+            PixelFormat::Argb => 0xFF << 24 | rgb,
+        }
+    }
+}
+
+/// White pixels on black, with shades of gray for the second plane.
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            colors: [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555],
+        }
+    }
+}
+
+/// An area of the display, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    // The smallest rectangle holding both rectangles
+    fn union(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+
+        Rect {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
 
-/// Represents the pixels of the 64x32 CHIP-8 display.
+/// Represents the pixels of the CHIP-8 display.
+///
+/// The display starts in the 64x32 low resolution mode and can be switched
+/// to the 128x64 Super-Chip high resolution mode at runtime.
+///
+/// Each pixel holds two bitplanes as used by XO-CHIP. CHIP-8 and Super-Chip programs
+/// only ever draw to the first plane. Drawing, clearing and scrolling only affect the
+/// currently selected planes, which defaults to the first one.
 ///
 /// A pixel is drawn when equal to true. When rendering it, make sure to
 /// scale it to improve visibility in modern screens. See the
 /// examples provided for reference.
+///
+/// The area changed since the last call to [`Screen::take_dirty`] is tracked, so frontends
+/// can redraw only that part of the display.
+#[derive(Clone)]
 pub struct Screen {
-    screen: Vec<bool>,
-    /// How many pixels wide the display is (64 for CHIP-8)
+    screen: Vec<u8>,
+    /// How many pixels wide the display is (64 for CHIP-8, 128 in high resolution)
     pub width: usize,
-    /// How many pixel high the display is (32 for CHIP-8)
+    /// How many pixel high the display is (32 for CHIP-8, 64 in high resolution)
     pub height: usize,
+    hires: bool,
+    planes: u8,
+    dirty: Option<Rect>,
 }
 
 impl Default for Screen {
     fn default() -> Self {
         Screen {
-            screen: vec![false; CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT],
+            screen: vec![0; CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT],
             width: CHIP8_SCREEN_WIDTH,
             height: CHIP8_SCREEN_HEIGHT,
+            hires: false,
+            planes: 0b01,
+            dirty: Some(Rect {
+                x: 0,
+                y: 0,
+                width: CHIP8_SCREEN_WIDTH,
+                height: CHIP8_SCREEN_HEIGHT,
+            }),
         }
     }
 }
 
 impl Screen {
-    /// Clear all pixels in the screen
+    /// Clear all pixels of the selected planes in the screen
     pub fn clear_screen(&mut self) {
-        self.screen.fill_with(|| false);
+        let planes = self.planes;
+        self.screen.iter_mut().for_each(|pixel| *pixel &= !planes);
+        self.mark_all_dirty();
+    }
+
+    /// Switch between the 64x32 and the 128x64 resolution. The screen is cleared
+    /// whenever the resolution changes.
+    pub fn set_hires(&mut self, hires: bool) {
+        let (width, height) = match hires {
+            true => (SCHIP_SCREEN_WIDTH, SCHIP_SCREEN_HEIGHT),
+            false => (CHIP8_SCREEN_WIDTH, CHIP8_SCREEN_HEIGHT),
+        };
+
+        self.hires = hires;
+        self.width = width;
+        self.height = height;
+        self.screen = vec![0; width * height];
+        self.mark_all_dirty();
     }
 
-    /// Get the state of the pixel at the provided coordinates.
+    /// Get the area changed since the last call, or None if nothing changed.
+    ///
+    /// A new screen is dirty as a whole. Changing the resolution, clearing and scrolling
+    /// mark the whole screen as changed.
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    /// Returns true when the screen is in the 128x64 high resolution mode.
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Select which bitplanes are affected by drawing, clearing and scrolling.
+    ///
+    /// Bit 0 selects the first plane and bit 1 the second one. Any other bits are ignored.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ALL_PLANES;
+    }
+
+    /// Get the bitplanes that are currently selected.
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Get the state of the pixel at the provided coordinates. The pixel is drawn
+    /// if it is set in any of the planes.
     pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.screen[x + y * self.width] != 0
+    }
+
+    /// Get the color of the pixel at the provided coordinates as an index between 0 and 3.
+    ///
+    /// Bit 0 holds the state of the first plane and bit 1 the state of the second one.
+    pub fn get_pixel_color(&self, x: usize, y: usize) -> u8 {
         self.screen[x + y * self.width]
     }
 
+    /// Get the state of the pixel at the provided coordinates in a single plane.
+    pub fn get_plane_pixel(&self, x: usize, y: usize, plane: u8) -> bool {
+        self.screen[x + y * self.width] & plane != 0
+    }
+
     /// Flip the state of the pixel at the provided coordinates
     pub fn toggle_pixel(&mut self, x: usize, y: usize) {
-        let idx = x + y * self.width;
-        self.screen[idx] = !self.screen[idx];
+        self.toggle_plane_pixel(x, y, self.planes);
+    }
+
+    /// Flip the state of the pixel at the provided coordinates in a single plane.
+    pub fn toggle_plane_pixel(&mut self, x: usize, y: usize, plane: u8) {
+        let pixel = self.screen[x + y * self.width] ^ (plane & ALL_PLANES);
+        self.update_pixel(x, y, pixel);
     }
 
     /// Set the pixel at the provided coordinates
     pub fn set_pixel(&mut self, x: usize, y: usize) {
-        self.screen[x + y * self.width] = true;
+        let pixel = self.screen[x + y * self.width] | self.planes;
+        self.update_pixel(x, y, pixel);
     }
 
     /// Clear the pixel at the provided coordinates
     pub fn clear_pixel(&mut self, x: usize, y: usize) {
-        self.screen[x + y * self.width] = false;
+        let pixel = self.screen[x + y * self.width] & !self.planes;
+        self.update_pixel(x, y, pixel);
+    }
+
+    // Change a pixel and mark it as dirty if it is different
+    fn update_pixel(&mut self, x: usize, y: usize, pixel: u8) {
+        let idx = x + y * self.width;
+        if self.screen[idx] == pixel {
+            return;
+        }
+
+        self.screen[idx] = pixel;
+        let area = Rect {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        };
+        self.dirty = Some(self.dirty.map_or(area, |dirty| dirty.union(area)));
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.dirty = Some(Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        });
+    }
+
+    /// Write the display into a buffer of `width * scale` by `height * scale` pixels, where
+    /// every pixel of the display becomes a square of `scale` by `scale` pixels.
+    ///
+    /// The buffer is laid out row by row. Returns a [`ChipError::InvalidBufferSize`] if the
+    /// buffer doesn't have the exact size.
+    pub fn render(
+        &self,
+        buffer: &mut [u32],
+        scale: usize,
+        palette: &Palette,
+        format: PixelFormat,
+    ) -> Result<(), ChipError> {
+        let row_size = self.width * scale;
+        let required = row_size * self.height * scale;
+        if buffer.len() != required {
+            return Err(ChipError::InvalidBufferSize {
+                size: buffer.len(),
+                required,
+            });
+        }
+        if scale == 0 {
+            return Ok(());
+        }
+
+        for (y, rows) in buffer.chunks_exact_mut(row_size * scale).enumerate() {
+            let (row, copies) = rows.split_at_mut(row_size);
+            for (x, pixels) in row.chunks_exact_mut(scale).enumerate() {
+                pixels.fill(palette.pixel(self.get_pixel_color(x, y), format));
+            }
+            for copy in copies.chunks_exact_mut(row_size) {
+                copy.copy_from_slice(row);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move every pixel up by the provided amount of rows. The rows
+    /// uncovered at the bottom are cleared.
+    pub fn scroll_up(&mut self, rows: usize) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pixel = match y + rows < self.height {
+                    true => self.get_pixel_color(x, y + rows),
+                    false => 0,
+                };
+                self.move_pixel(x, y, pixel);
+            }
+        }
+
+        self.mark_all_dirty();
+    }
+
+    /// Move every pixel down by the provided amount of rows. The rows
+    /// uncovered at the top are cleared.
+    pub fn scroll_down(&mut self, rows: usize) {
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let pixel = match y >= rows {
+                    true => self.get_pixel_color(x, y - rows),
+                    false => 0,
+                };
+                self.move_pixel(x, y, pixel);
+            }
+        }
+
+        self.mark_all_dirty();
+    }
+
+    /// Move every pixel right by the provided amount of columns. The columns
+    /// uncovered on the left are cleared.
+    pub fn scroll_right(&mut self, columns: usize) {
+        for y in 0..self.height {
+            for x in (0..self.width).rev() {
+                let pixel = match x >= columns {
+                    true => self.get_pixel_color(x - columns, y),
+                    false => 0,
+                };
+                self.move_pixel(x, y, pixel);
+            }
+        }
+
+        self.mark_all_dirty();
+    }
+
+    /// Move every pixel left by the provided amount of columns. The columns
+    /// uncovered on the right are cleared.
+    pub fn scroll_left(&mut self, columns: usize) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pixel = match x + columns < self.width {
+                    true => self.get_pixel_color(x + columns, y),
+                    false => 0,
+                };
+                self.move_pixel(x, y, pixel);
+            }
+        }
+
+        self.mark_all_dirty();
+    }
+
+    // Replace the selected planes of a pixel with the ones from another pixel
+    fn move_pixel(&mut self, x: usize, y: usize, pixel: u8) {
+        let idx = x + y * self.width;
+        self.screen[idx] = (self.screen[idx] & !self.planes) | (pixel & self.planes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let mut screen = Screen::default();
+        screen.set_pixel(1, 0);
+        screen.toggle_plane_pixel(0, 1, 0b10);
+        let palette = Palette {
+            colors: [0x000000, 0x112233, 0x445566, 0x778899],
+        };
+
+        let mut buffer = vec![0; 64 * 2 * 32 * 2];
+        screen
+            .render(&mut buffer, 2, &palette, PixelFormat::Rgba)
+            .unwrap();
+        assert_eq!(
+            buffer[0..4],
+            [0x000000FF, 0x000000FF, 0x112233FF, 0x112233FF]
+        );
+        assert_eq!(
+            buffer[128..132],
+            [0x000000FF, 0x000000FF, 0x112233FF, 0x112233FF]
+        );
+        assert_eq!(buffer[256..258], [0x445566FF, 0x445566FF]);
+
+        let mut buffer = vec![0; 64 * 32];
+        screen
+            .render(&mut buffer, 1, &palette, PixelFormat::Argb)
+            .unwrap();
+        assert_eq!(buffer[1], 0xFF112233);
+
+        let e = screen.render(&mut buffer, 2, &palette, PixelFormat::Argb);
+        assert!(matches!(
+            e,
+            Err(ChipError::InvalidBufferSize {
+                size: 2048,
+                required: 8192
+            })
+        ));
+    }
+
+    #[test]
+    fn dirty() {
+        let mut screen = Screen::default();
+        assert_eq!(screen.take_dirty().map(|dirty| dirty.width), Some(64));
+        assert_eq!(screen.take_dirty(), None);
+
+        screen.toggle_pixel(3, 4);
+        screen.set_pixel(10, 2);
+        screen.clear_pixel(20, 20);
+        assert_eq!(
+            screen.take_dirty(),
+            Some(Rect {
+                x: 3,
+                y: 2,
+                width: 8,
+                height: 3
+            })
+        );
+
+        screen.scroll_left(4);
+        assert_eq!(screen.take_dirty().map(|dirty| dirty.height), Some(32));
+        screen.set_hires(true);
+        screen.clear_screen();
+        assert_eq!(screen.take_dirty().map(|dirty| dirty.width), Some(128));
     }
 }
@@ -0,0 +1,428 @@
+use crate::errors::ChipError;
+use crate::{Chip8, Cpu, Random, Screen};
+
+const MAGIC: &[u8; 4] = b"SCH8";
+// Stored when FX0A isn't waiting for a key to be released
+const NO_KEY: u8 = 0xFF;
+
+/// The format version written by [`Chip8::save_state`].
+pub const SAVE_STATE_VERSION: u16 = 1;
+
+impl Chip8 {
+    /// Capture the whole machine in a byte blob that can be restored with [`Chip8::load_state`].
+    ///
+    /// The blob starts with a magic number and the format version, followed by tagged
+    /// sections holding the CPU, the random number generator, memory, screen and the
+    /// loaded ROM. The [Config](crate::Config) is not part of the save state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.write_state(&mut data, true);
+
+        data
+    }
+
+    // Replaces the content of the buffer with the save state, leaving out the ROM when it
+    // isn't needed, such as for the frames recorded for rewinding
+    pub(crate) fn write_state(&self, data: &mut Vec<u8>, with_rom: bool) {
+        data.clear();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&SAVE_STATE_VERSION.to_le_bytes());
+
+        write_section(data, b"REGS", |data| save_registers(&self.cpu, data));
+        write_section(data, b"TIMR", |data| {
+            data.extend_from_slice(&[self.cpu.timer_delay, self.cpu.timer_sound])
+        });
+        write_section(data, b"KEYS", |data| {
+            data.extend(self.cpu.keypad.map(|key| key as u8));
+            data.extend_from_slice(&self.cpu.key_presses.to_le_bytes());
+            data.extend_from_slice(&self.cpu.key_releases.to_le_bytes());
+        });
+        write_section(data, b"RPL ", |data| data.extend_from_slice(&self.cpu.rpl));
+        write_section(data, b"EXEC", |data| {
+            data.extend_from_slice(&[
+                self.cpu.halted as u8,
+                self.cpu.waiting_vblank as u8,
+                self.cpu.pressed_key.unwrap_or(NO_KEY),
+            ])
+        });
+        write_section(data, b"AUDI", |data| save_audio(&self.cpu, data));
+        write_section(data, b"RAND", |data| {
+            data.extend_from_slice(&self.cpu.random.state().to_le_bytes())
+        });
+        write_section(data, b"MEM ", |data| data.extend_from_slice(&self.memory));
+        write_section(data, b"SCRN", |data| save_screen(&self.screen, data));
+        if with_rom {
+            write_section(data, b"ROM ", |data| data.extend_from_slice(&self.rom));
+        }
+    }
+
+    /// Restore the machine from a blob created by [`Chip8::save_state`].
+    ///
+    /// The machine is left untouched if the blob is invalid. The memory of the save state
+    /// must have the size set by [`Config::memory_size`](crate::Config::memory_size).
+    /// The sound queued for [`Chip8::render_audio`], the time carried over by
+    /// [`Chip8::run_for`] and the frames recorded for [`Chip8::rewind`] are discarded.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), ChipError> {
+        self.restore_state(data)?;
+        self.rewind.clear();
+
+        Ok(())
+    }
+
+    // Loads a save state without touching the frames recorded for rewinding
+    pub(crate) fn restore_state(&mut self, data: &[u8]) -> Result<(), ChipError> {
+        let mut reader = Reader::new(data);
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(invalid("missing save state header"));
+        }
+
+        let version = reader.u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(ChipError::UnsupportedSaveStateVersion {
+                version,
+                supported: SAVE_STATE_VERSION,
+            });
+        }
+
+        let mut sections = Vec::new();
+        while !reader.is_empty() {
+            let tag = reader.bytes(4)?;
+            let len = reader.u32()? as usize;
+            sections.push((tag, reader.bytes(len)?));
+        }
+        let section = |tag: &[u8; 4]| sections.iter().find(|(t, _)| t == tag).map(|(_, s)| *s);
+        let read = |tag: &[u8; 4]| {
+            let data = section(tag).ok_or_else(|| {
+                invalid(&format!("missing {} section", String::from_utf8_lossy(tag)))
+            })?;
+
+            Ok(SectionReader {
+                tag: *tag,
+                reader: Reader::new(data),
+            })
+        };
+
+        let memory = read(b"MEM ")?.finish(|reader| Ok(reader.rest().to_vec()))?;
+        if memory.len() != self.config.memory_size {
+            return Err(invalid(&format!(
+                "memory size is {} bytes instead of {}",
+                memory.len(),
+                self.config.memory_size
+            )));
+        }
+        // Start from a reset copy so anything outside the machine state, such as the trace
+        // hook, is kept
+        let mut cpu = self.cpu.clone();
+        cpu.reset();
+        read(b"REGS")?.finish(|reader| load_registers(&mut cpu, reader, memory.len()))?;
+        read(b"TIMR")?.finish(|reader| {
+            cpu.timer_delay = reader.u8()?;
+            cpu.timer_sound = reader.u8()?;
+            Ok(())
+        })?;
+        read(b"KEYS")?.finish(|reader| {
+            let keys = reader.bytes(cpu.keypad.len())?;
+            cpu.keypad = std::array::from_fn(|key| keys[key] != 0);
+            cpu.key_presses = reader.u16()?;
+            cpu.key_releases = reader.u16()?;
+            Ok(())
+        })?;
+        read(b"RPL ")?.finish(|reader| {
+            let len = cpu.rpl.len();
+            cpu.rpl.copy_from_slice(reader.bytes(len)?);
+            Ok(())
+        })?;
+        read(b"EXEC")?.finish(|reader| {
+            cpu.halted = reader.u8()? != 0;
+            cpu.waiting_vblank = reader.u8()? != 0;
+            cpu.pressed_key = Some(reader.u8()?).filter(|&key| key != NO_KEY);
+            Ok(())
+        })?;
+        read(b"AUDI")?.finish(|reader| load_audio(&mut cpu, reader))?;
+        read(b"RAND")?.finish(|reader| {
+            cpu.random = Random::new(reader.u64()?);
+            Ok(())
+        })?;
+        let screen = read(b"SCRN")?.finish(load_screen)?;
+        // The frames recorded for rewinding don't hold the ROM, which never changes
+        let rom = section(b"ROM ").map_or_else(|| self.rom.clone(), |rom| rom.to_vec());
+
+        self.cpu = cpu;
+        self.memory = memory;
+        self.screen = screen;
+        self.rom = rom;
+        self.clock.reset();
+        self.audio.reset();
+
+        Ok(())
+    }
+}
+
+fn write_section(data: &mut Vec<u8>, tag: &[u8; 4], write: impl FnOnce(&mut Vec<u8>)) {
+    data.extend_from_slice(tag);
+    // The length is filled in once the section is written
+    let len_offset = data.len();
+    data.extend_from_slice(&[0; 4]);
+    write(data);
+    let len = (data.len() - len_offset - 4) as u32;
+    data[len_offset..len_offset + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+fn save_registers(cpu: &Cpu, data: &mut Vec<u8>) {
+    data.extend_from_slice(&cpu.v);
+    data.extend_from_slice(&cpu.i.to_le_bytes());
+    data.extend_from_slice(&(cpu.pc as u32).to_le_bytes());
+    data.push(cpu.sp as u8);
+    for value in cpu.stack {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn load_registers(cpu: &mut Cpu, reader: &mut Reader, memory_size: usize) -> Result<(), ChipError> {
+    let len = cpu.v.len();
+    cpu.v.copy_from_slice(reader.bytes(len)?);
+    cpu.i = reader.u16()?;
+    cpu.pc = reader.u32()? as usize;
+    cpu.sp = reader.u8()? as usize;
+    for value in cpu.stack.iter_mut() {
+        *value = reader.u16()?;
+    }
+
+    if cpu.pc > memory_size {
+        return Err(invalid("program counter is out of bounds"));
+    }
+    if cpu.sp >= cpu.stack.len() {
+        return Err(invalid("stack pointer is out of bounds"));
+    }
+
+    Ok(())
+}
+
+fn save_audio(cpu: &Cpu, data: &mut Vec<u8>) {
+    data.extend_from_slice(&cpu.audio_pattern);
+    data.push(cpu.pitch);
+    data.push(cpu.pattern_loaded as u8);
+}
+
+fn load_audio(cpu: &mut Cpu, reader: &mut Reader) -> Result<(), ChipError> {
+    let len = cpu.audio_pattern.len();
+    cpu.audio_pattern.copy_from_slice(reader.bytes(len)?);
+    cpu.pitch = reader.u8()?;
+    cpu.pattern_loaded = reader.u8()? != 0;
+
+    Ok(())
+}
+
+fn save_screen(screen: &Screen, data: &mut Vec<u8>) {
+    data.extend_from_slice(&[screen.is_hires() as u8, screen.selected_planes()]);
+    for y in 0..screen.height {
+        for x in 0..screen.width {
+            data.push(screen.get_pixel_color(x, y));
+        }
+    }
+}
+
+fn load_screen(reader: &mut Reader) -> Result<Screen, ChipError> {
+    let mut screen = Screen::default();
+    screen.set_hires(reader.u8()? != 0);
+    let planes = reader.u8()?;
+
+    let pixels = reader.bytes(screen.width * screen.height)?;
+    for y in 0..screen.height {
+        for x in 0..screen.width {
+            screen.toggle_plane_pixel(x, y, pixels[x + y * screen.width]);
+        }
+    }
+    screen.select_planes(planes);
+
+    Ok(screen)
+}
+
+fn invalid(reason: &str) -> ChipError {
+    ChipError::InvalidSaveState(reason.to_string())
+}
+
+// Reads a whole section, failing when data is left over once it is parsed
+struct SectionReader<'a> {
+    tag: [u8; 4],
+    reader: Reader<'a>,
+}
+
+impl<'a> SectionReader<'a> {
+    fn finish<T>(
+        mut self,
+        parse: impl FnOnce(&mut Reader<'a>) -> Result<T, ChipError>,
+    ) -> Result<T, ChipError> {
+        let value = parse(&mut self.reader)?;
+        if !self.reader.is_empty() {
+            return Err(invalid(&format!(
+                "{} section is too long",
+                String::from_utf8_lossy(&self.tag)
+            )));
+        }
+
+        Ok(value)
+    }
+}
+
+// Reads little endian values from a save state, failing when running out of data
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ChipError> {
+        if len > self.data.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ChipError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ChipError> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChipError> {
+        let bytes = self.bytes(4)?;
+
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, ChipError> {
+        let bytes = self.bytes(8)?;
+
+        Ok(u64::from_le_bytes(std::array::from_fn(|i| bytes[i])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn save_and_load() {
+        let mut c8 = Chip8::new(Config::xo_chip());
+        c8.load_rom(&[0x00, 0xFF, 0xF3, 0x01, 0x60, 0x12, 0x22, 0x00])
+            .unwrap();
+        for _ in 0..4 {
+            c8.step().unwrap();
+        }
+        c8.screen.set_pixel(100, 50);
+        c8.cpu.timer_sound = 7;
+        c8.cpu.keypad[0xA] = true;
+        c8.cpu.pattern_loaded = true;
+        let state = c8.save_state();
+
+        let mut restored = Chip8::new(Config::xo_chip());
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.memory, c8.memory);
+        assert_eq!(restored.cpu.v, c8.cpu.v);
+        assert_eq!(restored.cpu.pc, 0x200);
+        assert_eq!(restored.cpu.sp, 1);
+        assert_eq!(restored.cpu.stack[1], 0x208);
+        assert_eq!(restored.cpu.timer_sound, 7);
+        assert!(restored.cpu.keypad[0xA]);
+        assert!(restored.screen.is_hires());
+        assert_eq!(restored.screen.selected_planes(), 0b11);
+        assert_eq!(restored.screen.get_pixel_color(100, 50), 0b11);
+        assert_eq!(restored.cpu.random, c8.cpu.random);
+        assert!(restored.cpu.pattern_loaded);
+        assert_eq!(restored.save_state(), state);
+
+        restored.reset();
+        assert_eq!(restored.memory[0x200..0x208], c8.memory[0x200..0x208]);
+    }
+
+    #[test]
+    fn deterministic_load() {
+        let config = Config {
+            rewind_depth: 10,
+            ..Default::default()
+        };
+        let mut c8 = Chip8::new(config.clone());
+        c8.load_rom(&[0x60, 0x20, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        c8.tick().unwrap();
+        let state = c8.save_state();
+
+        let mut fresh = Chip8::new(config);
+        fresh.load_state(&state).unwrap();
+
+        c8.run_for(Duration::from_micros(8000)).unwrap();
+        c8.load_state(&state).unwrap();
+        assert_eq!(c8.rewind(1).unwrap(), 0);
+
+        let mut samples = [0.0; 64];
+        let mut fresh_samples = [0.0; 64];
+        c8.render_audio(48000, &mut samples);
+        fresh.render_audio(48000, &mut fresh_samples);
+        assert_eq!(samples, fresh_samples);
+
+        for _ in 0..3 {
+            c8.run_for(Duration::from_micros(8000)).unwrap();
+            fresh.run_for(Duration::from_micros(8000)).unwrap();
+        }
+        assert_eq!(c8.save_state(), fresh.save_state());
+    }
+
+    #[test]
+    fn invalid_state() {
+        let mut c8 = Chip8::default();
+        let mut state = c8.save_state();
+
+        let e = c8.load_state(&state[..state.len() - 1]);
+        assert!(matches!(e, Err(ChipError::InvalidSaveState(_))));
+
+        let e = c8.load_state(b"ROM data");
+        assert!(matches!(e, Err(ChipError::InvalidSaveState(_))));
+
+        let e = Chip8::new(Config::xo_chip()).load_state(&state);
+        assert!(matches!(e, Err(ChipError::InvalidSaveState(_))));
+
+        // A section with data left over
+        let timers = state.windows(4).position(|tag| tag == b"TIMR").unwrap();
+        let mut long = state.clone();
+        long[timers + 4] = 3;
+        long.insert(timers + 10, 0);
+        let e = c8.load_state(&long);
+        assert!(matches!(e, Err(ChipError::InvalidSaveState(_))));
+
+        // A missing section
+        let random = state.windows(4).position(|tag| tag == b"RAND").unwrap();
+        let mut missing = state.clone();
+        missing[random..random + 4].copy_from_slice(b"XXXX");
+        let e = c8.load_state(&missing);
+        assert!(matches!(e, Err(ChipError::InvalidSaveState(_))));
+
+        state[4] = 0xFF;
+        let e = c8.load_state(&state);
+        assert!(matches!(
+            e,
+            Err(ChipError::UnsupportedSaveStateVersion { version: 0xFF, .. })
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+// 1802 machine cycle costs of the instructions in the COSMAC VIP interpreter
+
+use crate::Instruction;
+
+/// The 1802 of the COSMAC VIP runs at 1.7609 MHz and a machine cycle takes 8 clock cycles.
+pub const MACHINE_CYCLES_PER_SECOND: u128 = 1_760_900 / 8;
+/// A 60 Hz frame of the display.
+pub const MACHINE_CYCLES_PER_FRAME: u32 = 3668;
+/// The cycles of every frame taken by the display DMA and the interrupt routine, which
+/// also ticks the timers.
+pub const INTERRUPT_CYCLES: u32 = 1024 + 48;
+/// The cycles of every frame left to the interpreter.
+pub const INTERPRETER_CYCLES_PER_FRAME: u32 = MACHINE_CYCLES_PER_FRAME - INTERRUPT_CYCLES;
+
+// Fetching and dispatching every instruction
+const FETCH_CYCLES: u32 = 40;
+// Clearing the 256 bytes of the display buffer, 12 cycles a byte
+const CLEAR_CYCLES: u32 = 24 + 256 * 12 + 6;
+// Setting up the sprite address and the display position
+const DRAW_CYCLES: u32 = 26;
+// Loading a sprite row, writing it to one byte and checking for a collision
+const ROW_CYCLES: u32 = 24;
+// Shifting a sprite row one bit to the right into the next byte
+const SHIFT_CYCLES: u32 = 8;
+// Writing the part of a shifted sprite row that lands in the next byte
+const SPLIT_ROW_CYCLES: u32 = 16;
+
+const DISPLAY_WIDTH: u32 = 64;
+const DISPLAY_HEIGHT: u32 = 32;
+
+/// The machine cycles taken by the instruction in the COSMAC VIP interpreter, given the
+/// registers before it was executed and whether it skipped the next instruction.
+pub fn machine_cycles(opcode: u16, v: &[u8; 16], skipped: bool) -> u32 {
+    // The skips increment the program counter a second time
+    let skip = |not_taken: u32| match skipped {
+        true => not_taken + 4,
+        false => not_taken,
+    };
+
+    let cycles = match Instruction::from(opcode) {
+        Instruction::Clear => CLEAR_CYCLES,
+        Instruction::Return => 10,
+        // The machine code runs outside of the interpreter
+        Instruction::MachineCall(_) => 12,
+        Instruction::Jump(_) => 12,
+        Instruction::Call(_) => 26,
+        Instruction::SkipEqualByte { .. } | Instruction::SkipNotEqualByte { .. } => skip(10),
+        Instruction::SkipEqual { .. } | Instruction::SkipNotEqual { .. } => skip(18),
+        Instruction::SetByte { .. } => 6,
+        Instruction::AddByte { .. } => 10,
+        // Every 8XYN assembles the 1802 arithmetic instruction in memory and runs it
+        Instruction::Set { .. }
+        | Instruction::Or { .. }
+        | Instruction::And { .. }
+        | Instruction::Xor { .. }
+        | Instruction::Add { .. }
+        | Instruction::Sub { .. }
+        | Instruction::ShiftRight { .. }
+        | Instruction::SubReverse { .. }
+        | Instruction::ShiftLeft { .. } => 44,
+        Instruction::SetI(_) => 12,
+        Instruction::JumpOffset(_) => 22,
+        Instruction::Random { .. } => 36,
+        Instruction::Draw { x, y, n } => draw_cycles(v[x as usize], v[y as usize], n),
+        Instruction::SkipKey(_) | Instruction::SkipNotKey(_) => skip(14),
+        Instruction::GetDelay(_) | Instruction::SetDelay(_) | Instruction::SetSound(_) => 10,
+        Instruction::WaitKey(_) => 20,
+        Instruction::AddI(_) | Instruction::Font(_) => 16,
+        // The digits are found by repeated subtraction
+        Instruction::Bcd(x) => 84 + 16 * digit_sum(v[x as usize]),
+        Instruction::Store(x) | Instruction::Retrieve(x) => 14 + 14 * (x as u32 + 1),
+        // The interpreter doesn't know the later instructions and only fetches them
+        _ => 0,
+    };
+
+    FETCH_CYCLES + cycles
+}
+
+// Every sprite row is shifted into place one bit at a time. Rows that aren't aligned to a
+// byte of the display buffer are split over two bytes, unless the second byte is past the
+// right edge. Rows past the bottom edge are clipped and cost nothing.
+fn draw_cycles(x: u8, y: u8, n: u8) -> u32 {
+    let (x, y) = (x as u32 % DISPLAY_WIDTH, y as u32 % DISPLAY_HEIGHT);
+    let shift = x % 8;
+    let rows = (n as u32).min(DISPLAY_HEIGHT - y);
+
+    let row = match shift {
+        0 => ROW_CYCLES,
+        _ if x / 8 == DISPLAY_WIDTH / 8 - 1 => ROW_CYCLES + SHIFT_CYCLES * shift,
+        _ => ROW_CYCLES + SHIFT_CYCLES * shift + SPLIT_ROW_CYCLES,
+    };
+
+    DRAW_CYCLES + rows * row
+}
+
+fn digit_sum(value: u8) -> u32 {
+    (value / 100 + value / 10 % 10 + value % 10) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn costs() {
+        let mut v = [0; 16];
+        assert_eq!(machine_cycles(0x00E0, &v, false), 40 + 3102);
+        assert_eq!(machine_cycles(0x00EE, &v, false), 50);
+        assert_eq!(machine_cycles(0x1200, &v, false), 52);
+        assert_eq!(machine_cycles(0x2200, &v, false), 66);
+        assert_eq!(machine_cycles(0x6012, &v, false), 46);
+        assert_eq!(machine_cycles(0x8014, &v, false), 84);
+        assert_eq!(machine_cycles(0xC0FF, &v, false), 76);
+
+        v[0] = 199;
+        assert_eq!(machine_cycles(0xF033, &v, false), 40 + 84 + 16 * 19);
+        assert_eq!(machine_cycles(0xF365, &v, false), 40 + 14 + 14 * 4);
+    }
+
+    #[test]
+    fn skip_costs() {
+        let v = [0; 16];
+        assert_eq!(machine_cycles(0x3012, &v, false), 50);
+        assert_eq!(machine_cycles(0x3012, &v, true), 54);
+        assert_eq!(machine_cycles(0x5010, &v, false), 58);
+        assert_eq!(machine_cycles(0x9010, &v, true), 62);
+        assert_eq!(machine_cycles(0xE0A1, &v, true), 58);
+    }
+
+    #[test]
+    fn draw_costs() {
+        let mut v = [0; 16];
+        assert_eq!(machine_cycles(0xD015, &v, false), 40 + 26 + 5 * 24);
+
+        // Unaligned rows are shifted and split over two bytes
+        v[0] = 3;
+        assert_eq!(
+            machine_cycles(0xD015, &v, false),
+            40 + 26 + 5 * (24 + 24 + 16)
+        );
+
+        // The second byte of rows at the right edge isn't written
+        v[0] = 61;
+        assert_eq!(machine_cycles(0xD015, &v, false), 40 + 26 + 5 * (24 + 40));
+
+        // Rows past the bottom edge are clipped
+        v[0] = 0;
+        v[1] = 30;
+        assert_eq!(machine_cycles(0xD015, &v, false), 40 + 26 + 2 * 24);
+        assert_eq!(machine_cycles(0xD010, &v, false), 40 + 26);
+    }
+}